@@ -0,0 +1,108 @@
+use mnemosyrs::pattern::Pattern;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+const HAYSTACK_SIZE: usize = 0x4000000;
+const ROUNDS: u32 = 5;
+
+// PatternMatch as it was before anchoring: the pattern split into bytes and a
+// mask, tried byte by byte at every offset by try_match_at_current_address.
+struct NaiveMatch {
+    pattern_size: usize,
+    byte_array: Vec<u8>,
+    mask: Vec<u8>,
+}
+
+impl NaiveMatch {
+    fn new(signature: &str) -> Self {
+        let pattern: String = signature
+            .trim_end_matches(|c: char| c == '?' || c.is_whitespace())
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        let mut naive = NaiveMatch {
+            pattern_size: pattern.len() / 2,
+            byte_array: vec![],
+            mask: vec![],
+        };
+
+        for n in (0..pattern.len()).step_by(2) {
+            if &pattern[n..n + 1] == "?" || &pattern[n + 1..n + 2] == "?" {
+                naive.mask.push(1);
+                naive.byte_array.push(0);
+            } else {
+                naive.mask.push(0);
+                naive
+                    .byte_array
+                    .push(u8::from_str_radix(&pattern[n..n + 2], 16).unwrap());
+            }
+        }
+
+        naive
+    }
+
+    // The original loop ran over the whole memory size and read past its end,
+    // here it stops where the pattern still fits.
+    fn find(&self, haystack: &[u8]) -> Option<usize> {
+        (0..=haystack.len() - self.pattern_size).find(|&offset| unsafe {
+            self.try_match_at_current_address(haystack.as_ptr().add(offset))
+        })
+    }
+
+    unsafe fn try_match_at_current_address(&self, current_address: *const u8) -> bool {
+        let mut j = 0;
+
+        while j < self.pattern_size
+            && (self.mask[j] == 0x01 || *current_address.add(j) ^ self.byte_array[j] == 0)
+        {
+            j += 1;
+        }
+
+        j == self.pattern_size
+    }
+}
+
+fn time(mut f: impl FnMut() -> Option<usize>) -> (Option<usize>, Duration) {
+    let start = Instant::now();
+    let mut found = None;
+
+    for _ in 0..ROUNDS {
+        found = std::hint::black_box(f());
+    }
+
+    (found, start.elapsed() / ROUNDS)
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0x6d6e656d);
+    let mut haystack = vec![0u8; HAYSTACK_SIZE];
+    rng.fill(&mut haystack[..]);
+
+    let needle = [
+        0x48, 0x8b, 0x05, 0x11, 0x22, 0x33, 0x44, 0x89, 0x45, 0xf8, 0xe8,
+    ];
+    let offset = HAYSTACK_SIZE - 0x100;
+    haystack[offset..offset + needle.len()].copy_from_slice(&needle);
+
+    for signature in [
+        "48 8b 05 ?? ?? ?? ?? 89 45 f8 e8",
+        "48 ?? 05 ?? ?? ?? ?? 89 ?? f8 e8",
+        "48 ?? 05 ?? 22 ?? 44 ?? 45 ?? e8",
+    ] {
+        let pattern = Pattern::parse(signature).unwrap();
+        let naive_match = NaiveMatch::new(signature);
+
+        let (naive, naive_time) = time(|| naive_match.find(&haystack));
+        let (anchored, anchored_time) = time(|| pattern.find(&haystack));
+        assert_eq!(naive, anchored);
+
+        println!(
+            "{:<36} naive {:>10.2?}  anchored {:>10.2?}  ({:.1}x)",
+            signature,
+            naive_time,
+            anchored_time,
+            naive_time.as_secs_f64() / anchored_time.as_secs_f64()
+        );
+    }
+}
@@ -1,103 +1,420 @@
-pub struct Address {
+#[cfg(target_os = "linux")]
+use crate::backend::Checked;
+use crate::backend::{Local, MemoryBackend};
+use crate::error::{Error, Result};
+use crate::post_op::PostOp;
+use crate::protection::{self, ProtectionGuard};
+
+// An address in the memory a backend reaches. The unsafe methods read or write
+// through the backend: with Local that is a plain dereference, with the other
+// backends a bad address comes back as an error.
+pub struct Address<B: MemoryBackend = Local> {
     ptr: *mut u8,
+    backend: B,
 }
 
+// ptr is only ever an address in the backend's address space, handed to the
+// backend to read or write, so an Address can move threads as its backend can.
+unsafe impl<B: MemoryBackend + Send> Send for Address<B> {}
+
 impl Address {
     pub fn new(ptr: *mut u8) -> Self {
-        Address { ptr: ptr }
+        Address {
+            ptr,
+            backend: Local,
+        }
     }
+}
 
-    pub unsafe fn read_memory(&mut self, size: usize) -> Vec<u8> {
-        let mut memory = vec![];
-        memory.reserve(size);
+#[cfg(target_os = "linux")]
+impl Address<Checked> {
+    pub fn checked(ptr: *mut u8) -> Self {
+        Address {
+            ptr,
+            backend: Checked,
+        }
+    }
+}
+
+impl<B: MemoryBackend> Address<B> {
+    pub fn with_backend(backend: B, ptr: *mut u8) -> Self {
+        Address { ptr, backend }
+    }
+
+    pub fn ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
 
-        for i in 0..size {
-            memory.push(*self.ptr.add(i));
+    unsafe fn read_exact(&self, address: usize, buffer: &mut [u8]) -> Result<()> {
+        let read = self.backend.read_bytes(address, buffer)?;
+        if read != buffer.len() {
+            return Err(Error::PartialRead {
+                address,
+                expected: buffer.len(),
+                read,
+            });
         }
 
-        memory
+        Ok(())
     }
 
-    pub unsafe fn write_memory(&mut self, bytes: &Vec<u8>) {
-        for i in 0..bytes.len() {
-            *self.ptr.add(i) = bytes[i];
+    unsafe fn write_all(&self, address: usize, bytes: &[u8]) -> Result<()> {
+        let written = self.backend.write_bytes(address, bytes)?;
+        if written != bytes.len() {
+            return Err(Error::PartialWrite {
+                address,
+                expected: bytes.len(),
+                written,
+            });
+        }
+
+        Ok(())
+    }
+
+    unsafe fn read_usize(&self, address: usize) -> Result<usize> {
+        if address == 0 {
+            return Err(Error::NullPointer);
+        }
+
+        let mut buffer = [0u8; std::mem::size_of::<usize>()];
+        self.read_exact(address, &mut buffer)?;
+        Ok(usize::from_ne_bytes(buffer))
+    }
+
+    unsafe fn read_value<T>(&self, address: usize) -> Result<T> {
+        let mut value = std::mem::MaybeUninit::<T>::uninit();
+        let buffer =
+            std::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, std::mem::size_of::<T>());
+        self.read_exact(address, buffer)?;
+        Ok(value.assume_init())
+    }
+
+    unsafe fn write_value<T>(&self, address: usize, value: T) -> Result<()> {
+        let bytes = std::slice::from_raw_parts(
+            std::ptr::addr_of!(value) as *const u8,
+            std::mem::size_of::<T>(),
+        );
+        let result = self.write_all(address, bytes);
+        std::mem::forget(value);
+        result
+    }
+
+    // Up to `max_units` units of `unit` bytes from self.ptr, stopping before the
+    // first unit that is all zero. Reads never cross into the next page before
+    // the current one has been searched, not even for a unit straddling the two,
+    // and a failed read past the first one ends the string, so a string ending
+    // just before unmapped memory reads fine.
+    unsafe fn read_terminated(&self, unit: usize, max_units: usize) -> Result<Vec<u8>> {
+        let page_size = protection::page_size();
+        let start = self.ptr as usize;
+        // no further than the end of the address space
+        let max_len = max_units.saturating_mul(unit).min(usize::MAX - start);
+        let mut bytes = vec![];
+
+        while bytes.len() < max_len {
+            let address = start + bytes.len();
+            let chunk = (page_size - address % page_size).min(max_len - bytes.len());
+
+            let length = bytes.len();
+            bytes.resize(length + chunk, 0);
+            let read = match self.backend.read_bytes(address, &mut bytes[length..]) {
+                Ok(read) => read,
+                Err(_) if length > 0 => 0,
+                Err(error) => return Err(error),
+            };
+            bytes.truncate(length + read);
+
+            let whole = bytes.len() - bytes.len() % unit;
+            if let Some(end) = bytes[length - length % unit..whole]
+                .chunks_exact(unit)
+                .position(|unit| unit.iter().all(|byte| *byte == 0))
+            {
+                bytes.truncate(length - length % unit + end * unit);
+                return Ok(bytes);
+            }
+
+            if read < chunk {
+                if bytes.len() < unit {
+                    return Err(Error::PartialRead {
+                        address: start,
+                        expected: unit,
+                        read: 0,
+                    });
+                }
+                break;
+            }
+        }
+
+        bytes.truncate(bytes.len() - bytes.len() % unit);
+        Ok(bytes)
+    }
+
+    // [self.ptr] followed by every offset but the last, see *_multilevel_ptr_val.
+    unsafe fn resolve_multilevel_ptr(&self, offsets: &[usize]) -> Result<usize> {
+        if offsets.is_empty() {
+            return Err(Error::NoOffsets);
+        }
+
+        let mut base = self.read_usize(self.ptr as usize)?;
+        for offset in &offsets[..offsets.len() - 1] {
+            if base == 0 {
+                return Err(Error::NullPointer);
+            }
+
+            // the for loop deref our base
+            base = self.read_usize(offset_address(base, *offset)?)?;
         }
+
+        if base == 0 {
+            return Err(Error::NullPointer);
+        }
+
+        offset_address(base, offsets[offsets.len() - 1])
+    }
+
+    // Runs `f` with the `size` bytes at self.ptr made writable for its duration,
+    // restoring their page protection afterwards.
+    /// # Safety
+    /// With Local, the `size` bytes at self.ptr must be mapped, and changing the
+    /// protection of their pages must not break other code using them.
+    pub unsafe fn try_with_write_access<R>(
+        &mut self,
+        size: usize,
+        f: impl FnOnce(&mut Address<&B>) -> Result<R>,
+    ) -> Result<R> {
+        let _guard = ProtectionGuard::writable(&self.backend, self.ptr as usize, size)?;
+        f(&mut Address::with_backend(&self.backend, self.ptr))
+    }
+
+    /// # Safety
+    /// With Local, the `size` bytes at self.ptr must be readable.
+    pub unsafe fn try_read_memory(&mut self, size: usize) -> Result<Vec<u8>> {
+        let mut memory = vec![0u8; size];
+        self.read_exact(self.ptr as usize, &mut memory)?;
+
+        Ok(memory)
+    }
+
+    /// # Safety
+    /// Same as try_read_memory.
+    pub unsafe fn read_memory(&mut self, size: usize) -> Vec<u8> {
+        self.try_read_memory(size).expect("failed to read memory")
+    }
+
+    /// # Safety
+    /// With Local, self.ptr must be valid for writes of `bytes.len()` bytes.
+    pub unsafe fn try_write_memory(&mut self, bytes: &[u8]) -> Result<()> {
+        self.write_all(self.ptr as usize, bytes)
+    }
+
+    /// # Safety
+    /// Same as try_write_memory.
+    #[allow(clippy::ptr_arg)]
+    pub unsafe fn write_memory(&mut self, bytes: &Vec<u8>) {
+        self.try_write_memory(bytes)
+            .expect("failed to write memory");
     }
 
+    /// # Safety
+    /// `bytes` must be valid for reads of `size` bytes, and with Local, self.ptr
+    /// for writes of as many.
+    pub unsafe fn try_copy_memory(&mut self, bytes: *const u8, size: usize) -> Result<()> {
+        self.try_write_memory(std::slice::from_raw_parts(bytes, size))
+    }
+
+    /// # Safety
+    /// Same as try_copy_memory.
     pub unsafe fn copy_memory(&mut self, bytes: *const u8, size: usize) {
-        std::ptr::copy_nonoverlapping(bytes, self.ptr, size);
+        self.try_copy_memory(bytes, size)
+            .expect("failed to write memory");
     }
 
+    /// # Safety
+    /// With Local, self.ptr must be valid for writes of `size` bytes.
+    pub unsafe fn try_fill_memory(&mut self, byte: u8, size: usize) -> Result<()> {
+        self.try_write_memory(&vec![byte; size])
+    }
+
+    /// # Safety
+    /// Same as try_fill_memory.
     pub unsafe fn fill_memory(&mut self, byte: u8, size: usize) {
-        std::ptr::write_bytes(self.ptr, byte, size);
+        self.try_fill_memory(byte, size)
+            .expect("failed to write memory");
+    }
+
+    /// # Safety
+    /// With Local, self.ptr must be valid for writes of a `T`. The bytes of `data`
+    /// are copied as they are, whatever they refer to must make sense at the target.
+    pub unsafe fn try_write<T>(&mut self, data: T) -> Result<()> {
+        self.write_value(self.ptr as usize, data)
     }
 
+    /// # Safety
+    /// Same as try_write.
     pub unsafe fn write<T>(&mut self, data: T) {
-        std::ptr::write::<T>(self.ptr as *mut T, data);
+        self.try_write(data).expect("failed to write memory");
     }
 
+    /// # Safety
+    /// With Local, self.ptr must be valid for reads of a `T`, and the bytes there
+    /// must be a valid `T`.
+    pub unsafe fn try_read<T>(&mut self) -> Result<T> {
+        self.read_value(self.ptr as usize)
+    }
+
+    /// # Safety
+    /// Same as try_read.
     pub unsafe fn read<T>(&mut self) -> T {
-        std::ptr::read::<T>(self.ptr as *mut T)
+        self.try_read().expect("failed to read memory")
     }
 
-    pub unsafe fn write_ptr_val<T>(&mut self, offset: usize, value: T) -> bool {
-        if self.ptr.is_null() {
-            return false;
-        }
+    // The NUL terminated string at self.ptr, at most `max_len` bytes of it when
+    // no terminator comes first. Invalid UTF-8 is replaced.
+    /// # Safety
+    /// With Local, the bytes up to the terminator or `max_len` must be readable.
+    pub unsafe fn try_read_cstring(&mut self, max_len: usize) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.read_terminated(1, max_len)?).into_owned())
+    }
+
+    /// # Safety
+    /// Same as try_read_cstring.
+    pub unsafe fn read_cstring(&mut self, max_len: usize) -> String {
+        self.try_read_cstring(max_len)
+            .expect("failed to read memory")
+    }
+
+    // The same for UTF-16LE, `max_len` counted in u16 units.
+    /// # Safety
+    /// Same as try_read_cstring.
+    pub unsafe fn try_read_utf16_string(&mut self, max_len: usize) -> Result<String> {
+        let units: Vec<u16> = self
+            .read_terminated(2, max_len)?
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+
+        Ok(String::from_utf16_lossy(&units))
+    }
 
+    /// # Safety
+    /// Same as try_read_cstring.
+    pub unsafe fn read_utf16_string(&mut self, max_len: usize) -> String {
+        self.try_read_utf16_string(max_len)
+            .expect("failed to read memory")
+    }
+
+    /// # Safety
+    /// With Local, self.ptr must hold a readable pointer, and the `T` at `offset`
+    /// from where it points must be valid for writes.
+    pub unsafe fn try_write_ptr_val<T>(&mut self, offset: usize, value: T) -> Result<()> {
         // [[self.ptr]+offset] = value, where [ptr] derefs ptr.
-        let ptr_to_val = *(self.ptr as *const usize) + offset;
-        *(ptr_to_val as *mut T) = value;
-        true
+        let ptr_to_val = self.resolve_multilevel_ptr(&[offset])?;
+        self.write_value(ptr_to_val, value)
     }
 
-    pub unsafe fn read_ptr_val<T: Clone>(&mut self, offset: usize) -> Option<T> {
-        if self.ptr.is_null() {
-            return None;
-        }
+    /// # Safety
+    /// Same as try_write_ptr_val.
+    pub unsafe fn write_ptr_val<T>(&mut self, offset: usize, value: T) -> bool {
+        self.try_write_ptr_val(offset, value).is_ok()
+    }
 
+    /// # Safety
+    /// With Local, self.ptr must hold a readable pointer, and the bytes at `offset`
+    /// from where it points must be a valid `T`.
+    pub unsafe fn try_read_ptr_val<T: Clone>(&mut self, offset: usize) -> Result<T> {
         // return = [[self.ptr]+offset], where [ptr] derefs ptr.
-        let ptr_to_val = *(self.ptr as *const usize) + offset;
-        return Some((*(ptr_to_val as *mut T)).clone());
+        let ptr_to_val = self.resolve_multilevel_ptr(&[offset])?;
+        self.read_value(ptr_to_val)
     }
 
-    pub unsafe fn write_multilevel_ptr_val<T>(&mut self, offsets: &Vec<usize>, value: T) -> bool {
-        if self.ptr.is_null() {
-            return false;
-        }
+    /// # Safety
+    /// Same as try_read_ptr_val.
+    pub unsafe fn read_ptr_val<T: Clone>(&mut self, offset: usize) -> Option<T> {
+        self.try_read_ptr_val(offset).ok()
+    }
 
-        let mut base = *(self.ptr as *const usize);
-        for i in 0..offsets.len() {
-            if i == offsets.len() - 1 {
-                let ptr_to_val = base + offsets[i];
-                *(ptr_to_val as *mut T) = value;
-                return true;
-            } else {
-                // the for loop deref our base
-                base = *((base + offsets[i]) as *const usize);
-            }
-        }
+    /// # Safety
+    /// With Local, every pointer along the chain must be readable, and the `T` at
+    /// its end valid for writes.
+    pub unsafe fn try_write_multilevel_ptr_val<T>(
+        &mut self,
+        offsets: &[usize],
+        value: T,
+    ) -> Result<()> {
+        let ptr_to_val = self.resolve_multilevel_ptr(offsets)?;
+        self.write_value(ptr_to_val, value)
+    }
 
-        false
+    /// # Safety
+    /// Same as try_write_multilevel_ptr_val.
+    #[allow(clippy::ptr_arg)]
+    pub unsafe fn write_multilevel_ptr_val<T>(&mut self, offsets: &Vec<usize>, value: T) -> bool {
+        self.try_write_multilevel_ptr_val(offsets, value).is_ok()
+    }
+
+    /// # Safety
+    /// With Local, every pointer along the chain must be readable, and the bytes at
+    /// its end a valid `T`.
+    pub unsafe fn try_read_multilevel_ptr_val<T: Clone>(&mut self, offsets: &[usize]) -> Result<T> {
+        let ptr_to_val = self.resolve_multilevel_ptr(offsets)?;
+        self.read_value(ptr_to_val)
     }
 
+    /// # Safety
+    /// Same as try_read_multilevel_ptr_val.
+    #[allow(clippy::ptr_arg)]
     pub unsafe fn read_multilevel_ptr_val<T: Clone>(&mut self, offsets: &Vec<usize>) -> Option<T> {
-        if self.ptr.is_null() {
-            return None;
-        }
+        self.try_read_multilevel_ptr_val(offsets).ok()
+    }
+}
 
-        let mut base = *(self.ptr as *const usize);
-        for i in 0..offsets.len() {
-            if i == offsets.len() - 1 {
-                let ptr_to_val = base + offsets[i];
-                return Some((*(ptr_to_val as *mut T)).clone());
-            } else {
-                // the for loop deref our base
-                base = *((base + offsets[i]) as *const usize);
-            }
+// Pointers read from the target may hold anything, so adding to them is checked.
+fn offset_address(address: usize, offset: usize) -> Result<usize> {
+    address
+        .checked_add(offset)
+        .ok_or(Error::AddressOverflow { address, offset })
+}
+
+impl<B: MemoryBackend + Clone> Address<B> {
+    /// # Safety
+    /// With Local, every byte the operations read must be readable.
+    pub unsafe fn try_resolve(&self, ops: &[PostOp]) -> Result<Address<B>> {
+        let mut address = self.ptr as usize;
+
+        for op in ops {
+            address = match *op {
+                PostOp::Add(offset) => address.wrapping_add_signed(offset),
+                PostOp::Relative { offset, length } => offset_address(address, length)?
+                    .wrapping_add_signed(
+                        self.read_value::<i32>(offset_address(address, offset)?)? as isize
+                    ),
+                PostOp::FollowBranch => match self.read_value::<u8>(address)? {
+                    0xe8 | 0xe9 => offset_address(address, 5)?.wrapping_add_signed(
+                        self.read_value::<i32>(offset_address(address, 1)?)? as isize,
+                    ),
+                    opcode => return Err(Error::UnexpectedInstruction { address, opcode }),
+                },
+                PostOp::Deref => match self.read_usize(address)? {
+                    0 => return Err(Error::NullPointer),
+                    pointer => pointer,
+                },
+            };
         }
 
-        None
+        Ok(Address::with_backend(
+            self.backend.clone(),
+            address as *mut u8,
+        ))
+    }
+
+    /// # Safety
+    /// Same as try_resolve.
+    pub unsafe fn resolve(&self, ops: &[PostOp]) -> Option<Address<B>> {
+        self.try_resolve(ops).ok()
     }
 }
 
@@ -140,6 +457,7 @@ mod unit_test {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_address_copy_memory() {
         std::env::set_var("RUST_BACKTRACE", "1");
 
@@ -231,6 +549,7 @@ mod unit_test {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_address_write_ptr_val() {
         let obj = test_struct {
             a: 0x33,
@@ -348,6 +667,7 @@ mod unit_test {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_address_write_multilevel_ptr_val() {
         let v1 = 0xc0cac0ca;
         let v2 = 0xbaadf00d;
@@ -522,4 +842,333 @@ mod unit_test {
             );
         }
     }
+
+    #[test]
+    fn test_address_try_read_multilevel_ptr_val() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let inner_obj = test_struct_multilevel_inner {
+            x: 0x11223344,
+            y: std::ptr::null(),
+            z: 0x56565656,
+        };
+        let ptr = std::ptr::addr_of!(inner_obj) as *mut u8;
+        let ptr_to_ptr = std::ptr::addr_of!(ptr) as *mut u8;
+
+        unsafe {
+            assert_eq!(
+                0x56565656,
+                Address::new(ptr_to_ptr)
+                    .try_read_multilevel_ptr_val::<u32>(&[offsetof!(
+                        test_struct_multilevel_inner,
+                        z
+                    )])
+                    .unwrap()
+            );
+            assert!(matches!(
+                Address::new(ptr_to_ptr).try_read_multilevel_ptr_val::<u32>(&[
+                    offsetof!(test_struct_multilevel_inner, y),
+                    4
+                ]),
+                Err(Error::NullPointer)
+            ));
+            assert!(matches!(
+                Address::new(ptr_to_ptr).try_write_multilevel_ptr_val::<u32>(
+                    &[offsetof!(test_struct_multilevel_inner, y), 4],
+                    0
+                ),
+                Err(Error::NullPointer)
+            ));
+            assert!(matches!(
+                Address::new(std::ptr::null_mut()).try_read_ptr_val::<u32>(0),
+                Err(Error::NullPointer)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_address_try_read_write_buffer() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = crate::backend::Buffer::new(0x1000, vec![0xef, 0xbe, 0xad, 0xde]);
+        let mut address = Address::with_backend(&buffer, 0x1002 as *mut u8);
+
+        unsafe {
+            assert_eq!(0xdead, address.try_read::<u16>().unwrap());
+            assert!(matches!(
+                address.try_read::<u32>(),
+                Err(Error::PartialRead {
+                    address: 0x1002,
+                    expected: 4,
+                    read: 2
+                })
+            ));
+            assert!(matches!(
+                address.try_write_memory(&[0, 0, 0]),
+                Err(Error::PartialWrite {
+                    address: 0x1002,
+                    expected: 3,
+                    written: 2
+                })
+            ));
+            assert!(matches!(
+                Address::with_backend(&buffer, 0x2000 as *mut u8).try_read_memory(1),
+                Err(Error::Unmapped(0x2000))
+            ));
+            assert!(matches!(
+                address.try_read_multilevel_ptr_val::<u8>(&[]),
+                Err(Error::NoOffsets)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_address_overflow() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = crate::backend::Buffer::new(0x1000, usize::MAX.to_ne_bytes().to_vec());
+        let address = Address::with_backend(&buffer, 0x1000 as *mut u8);
+
+        unsafe {
+            assert!(matches!(
+                Address::with_backend(&buffer, 0x1000 as *mut u8).try_read_ptr_val::<u8>(8),
+                Err(Error::AddressOverflow {
+                    address: usize::MAX,
+                    offset: 8
+                })
+            ));
+            assert!(matches!(
+                address.try_resolve(&[
+                    PostOp::Deref,
+                    PostOp::Relative {
+                        offset: 3,
+                        length: 7
+                    }
+                ]),
+                Err(Error::AddressOverflow {
+                    address: usize::MAX,
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_address_checked() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let v = 0xc0cac0cau32;
+        let inner_obj = test_struct_multilevel_inner {
+            x: 0x11223344,
+            y: std::ptr::addr_of!(v),
+            z: 0x56565656,
+        };
+        let stale_obj = test_struct_multilevel_inner {
+            x: 0,
+            y: 0x10 as *const u32,
+            z: 0,
+        };
+        let ptr = std::ptr::addr_of!(inner_obj) as *mut u8;
+        let ptr_to_ptr = std::ptr::addr_of!(ptr) as *mut u8;
+        let stale_ptr = std::ptr::addr_of!(stale_obj) as *mut u8;
+        let stale_ptr_to_ptr = std::ptr::addr_of!(stale_ptr) as *mut u8;
+
+        unsafe {
+            assert_eq!(
+                v,
+                Address::checked(ptr_to_ptr)
+                    .try_read_multilevel_ptr_val::<u32>(&[
+                        offsetof!(test_struct_multilevel_inner, y),
+                        0
+                    ])
+                    .unwrap()
+            );
+            assert!(matches!(
+                Address::checked(stale_ptr_to_ptr).try_read_multilevel_ptr_val::<u32>(&[
+                    offsetof!(test_struct_multilevel_inner, y),
+                    0
+                ]),
+                Err(Error::Unmapped(0x10))
+            ));
+            assert_eq!(
+                None,
+                Address::checked(0x10 as *mut u8).read_ptr_val::<u32>(0)
+            );
+            assert!(matches!(
+                Address::checked(0x10 as *mut u8).try_read_memory(4),
+                Err(Error::Unmapped(0x10))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_address_read_cstring() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = b"hello\0world".to_vec();
+        bytes.extend("h\u{e9}".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        bytes.extend_from_slice(&[0, 0, b'x', 0]);
+        let buffer = crate::backend::Buffer::new(0x1000, bytes);
+
+        unsafe {
+            let mut address = Address::with_backend(&buffer, 0x1000 as *mut u8);
+            assert_eq!("hello", address.read_cstring(64));
+            assert_eq!("hel", address.read_cstring(3));
+
+            // runs into the end of the buffer without a terminator
+            let mut address = Address::with_backend(&buffer, 0x1006 as *mut u8);
+            assert_eq!("world", address.read_cstring(5));
+
+            let mut address = Address::with_backend(&buffer, 0x100b as *mut u8);
+            assert_eq!("h\u{e9}", address.read_utf16_string(64));
+            assert_eq!("h", address.read_utf16_string(1));
+
+            let mut address = Address::with_backend(&buffer, 0x100f as *mut u8);
+            assert_eq!("", address.read_utf16_string(64));
+            let mut address = Address::with_backend(&buffer, 0x1011 as *mut u8);
+            assert_eq!("x", address.read_utf16_string(64));
+            // a zero byte only terminates as part of a zero unit
+            let mut address = Address::with_backend(&buffer, 0x1010 as *mut u8);
+            assert_eq!("\u{7800}", address.read_utf16_string(64));
+
+            assert!(matches!(
+                Address::with_backend(&buffer, 0x2000 as *mut u8).try_read_cstring(4),
+                Err(Error::Unmapped(0x2000))
+            ));
+
+            let mut address = Address::with_backend(&buffer, 0x1000 as *mut u8);
+            assert_eq!("hello", address.read_cstring(usize::MAX));
+            assert_eq!("hello", address.read_cstring(usize::MAX / 2 + 1));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_address_read_cstring_page_end() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let page_size = protection::page_size();
+
+        unsafe {
+            let page = libc::mmap(
+                std::ptr::null_mut(),
+                page_size * 2,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(libc::MAP_FAILED, page);
+            let page = page as usize;
+            assert_eq!(0, libc::munmap((page + page_size) as *mut _, page_size));
+
+            let end = page + page_size;
+            std::ptr::copy_nonoverlapping(b"abc".as_ptr(), (end - 3) as *mut u8, 3);
+
+            // no terminator before the unmapped page
+            assert_eq!(
+                "abc",
+                Address::checked((end - 3) as *mut u8).read_cstring(64)
+            );
+            // 'a' and its zero high byte, then a unit straddling into the hole
+            std::ptr::copy_nonoverlapping(b"a\0b".as_ptr(), (end - 3) as *mut u8, 3);
+            assert_eq!(
+                "a",
+                Address::checked((end - 3) as *mut u8).read_utf16_string(64)
+            );
+            assert!(matches!(
+                Address::checked(end as *mut u8).try_read_cstring(64),
+                Err(Error::Unmapped(_))
+            ));
+            assert!(Address::checked((end - 1) as *mut u8)
+                .try_read_utf16_string(64)
+                .is_err());
+
+            libc::munmap(page as *mut _, page_size);
+        }
+    }
+
+    #[test]
+    fn test_address_try_resolve() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        // 0x400000 mov rax, [rip + 0x10]
+        // 0x400007 call 0x400000
+        // 0x40000c jmp 0x400007
+        // 0x400011 cmp dword ptr [rip - 0x18], 1
+        // 0x400018 pointer to 0x400007, then a null pointer
+        let mut bytes = vec![
+            0x48, 0x8b, 0x05, 0x11, 0x00, 0x00, 0x00, 0xe8, 0xf4, 0xff, 0xff, 0xff, 0xe9, 0xf6,
+            0xff, 0xff, 0xff, 0x83, 0x3d, 0xe8, 0xff, 0xff, 0xff, 0x01,
+        ];
+        bytes.extend_from_slice(&0x400007usize.to_ne_bytes());
+        bytes.extend_from_slice(&0usize.to_ne_bytes());
+        let buffer = crate::backend::Buffer::new(0x400000, bytes);
+        let address = Address::with_backend(&buffer, 0x400000 as *mut u8);
+
+        unsafe {
+            assert_eq!(
+                0x400018 as *mut u8,
+                address.try_resolve(&[PostOp::rip(3)]).unwrap().ptr()
+            );
+            assert_eq!(
+                0x400007 as *mut u8,
+                address
+                    .try_resolve(&[PostOp::rip(3), PostOp::Deref])
+                    .unwrap()
+                    .ptr()
+            );
+            assert_eq!(
+                0x400000 as *mut u8,
+                address
+                    .try_resolve(&[PostOp::rip(3), PostOp::Deref, PostOp::FollowBranch])
+                    .unwrap()
+                    .ptr()
+            );
+            assert_eq!(
+                0x400007 as *mut u8,
+                address
+                    .try_resolve(&[PostOp::Add(0xc), PostOp::FollowBranch])
+                    .unwrap()
+                    .ptr()
+            );
+            // the displacement is followed by an imm8
+            assert_eq!(
+                0x400000 as *mut u8,
+                address
+                    .try_resolve(&[
+                        PostOp::Add(0x11),
+                        PostOp::Relative {
+                            offset: 2,
+                            length: 7
+                        }
+                    ])
+                    .unwrap()
+                    .ptr()
+            );
+            assert_eq!(
+                0x400000 as *mut u8,
+                address
+                    .try_resolve(&[PostOp::Add(4), PostOp::Add(-4)])
+                    .unwrap()
+                    .ptr()
+            );
+
+            assert!(matches!(
+                address.try_resolve(&[PostOp::FollowBranch]),
+                Err(Error::UnexpectedInstruction {
+                    address: 0x400000,
+                    opcode: 0x48
+                })
+            ));
+            assert!(matches!(
+                address.try_resolve(&[PostOp::Add(0x20), PostOp::Deref]),
+                Err(Error::NullPointer)
+            ));
+            assert!(address
+                .resolve(&[PostOp::Add(0x100), PostOp::Deref])
+                .is_none());
+        }
+    }
 }
@@ -0,0 +1,392 @@
+use crate::error::{Error, Result};
+use crate::regions::{MemoryRegion, Permissions};
+#[cfg(target_os = "linux")]
+use crate::{process::Process, protection, regions};
+use std::cell::RefCell;
+
+pub trait MemoryBackend {
+    /// # Safety
+    /// Backends that dereference `address` directly, like Local, need it to be
+    /// valid for reads of `buffer.len()` bytes.
+    unsafe fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize>;
+    /// # Safety
+    /// Backends that dereference `address` directly need it to be valid for writes
+    /// of `bytes.len()` bytes, and nothing may rely on what was there before.
+    unsafe fn write_bytes(&self, address: usize, bytes: &[u8]) -> Result<usize>;
+    fn regions(&self) -> Result<Vec<MemoryRegion>>;
+
+    // Backends whose writes are not held back by page protection keep this no-op.
+    /// # Safety
+    /// Taking write or read access away from memory that is in use makes its
+    /// users fault.
+    unsafe fn protect(
+        &self,
+        _address: usize,
+        _size: usize,
+        _permissions: Permissions,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn query_region(&self, address: usize) -> Result<Option<MemoryRegion>> {
+        Ok(self
+            .regions()?
+            .into_iter()
+            .find(|region| region.contains(address)))
+    }
+}
+
+impl<B: MemoryBackend + ?Sized> MemoryBackend for &B {
+    unsafe fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+        (**self).read_bytes(address, buffer)
+    }
+
+    unsafe fn write_bytes(&self, address: usize, bytes: &[u8]) -> Result<usize> {
+        (**self).write_bytes(address, bytes)
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        (**self).regions()
+    }
+
+    unsafe fn protect(&self, address: usize, size: usize, permissions: Permissions) -> Result<()> {
+        (**self).protect(address, size, permissions)
+    }
+
+    fn query_region(&self, address: usize) -> Result<Option<MemoryRegion>> {
+        (**self).query_region(address)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Local;
+
+impl MemoryBackend for Local {
+    unsafe fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+        std::ptr::copy_nonoverlapping(address as *const u8, buffer.as_mut_ptr(), buffer.len());
+        Ok(buffer.len())
+    }
+
+    unsafe fn write_bytes(&self, address: usize, bytes: &[u8]) -> Result<usize> {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), address as *mut u8, bytes.len());
+        Ok(bytes.len())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        regions::local()
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn protect(&self, address: usize, size: usize, permissions: Permissions) -> Result<()> {
+        protection::protect(address, size, permissions)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        Ok(vec![])
+    }
+}
+
+// Local memory accessed without ever faulting: reads go through process_vm_readv
+// on ourselves and writes are checked against our memory map first, so stale or
+// speculative pointers produce Unmapped/ProtectionFault instead of a SIGSEGV.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Checked;
+
+#[cfg(target_os = "linux")]
+impl MemoryBackend for Checked {
+    unsafe fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+        Process::current().read_bytes(address, buffer)
+    }
+
+    unsafe fn write_bytes(&self, address: usize, bytes: &[u8]) -> Result<usize> {
+        if bytes.is_empty() {
+            return Ok(0);
+        }
+
+        let regions = regions::local()?;
+        let mut accessible = 0;
+
+        while accessible < bytes.len() {
+            let current = address + accessible;
+            match regions.iter().find(|region| region.contains(current)) {
+                Some(region) if region.is_writable() => accessible = region.end - address,
+                Some(_) if accessible == 0 => return Err(Error::ProtectionFault(current)),
+                None if accessible == 0 => return Err(Error::Unmapped(current)),
+                _ => break,
+            }
+        }
+
+        Local.write_bytes(address, &bytes[..accessible.min(bytes.len())])
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        regions::local()
+    }
+
+    unsafe fn protect(&self, address: usize, size: usize, permissions: Permissions) -> Result<()> {
+        protection::protect(address, size, permissions)
+    }
+}
+
+// A flat byte buffer mapped at `base`, so scan and patch code can run against
+// dumps and test fixtures with the addresses they would have in the target.
+#[derive(Debug)]
+pub struct Buffer {
+    base: usize,
+    bytes: RefCell<Vec<u8>>,
+}
+
+impl Buffer {
+    pub fn new(base: usize, bytes: Vec<u8>) -> Self {
+        Buffer {
+            base,
+            bytes: RefCell::new(bytes),
+        }
+    }
+
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.borrow().is_empty()
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.bytes.borrow().clone()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.bytes.into_inner()
+    }
+
+    fn offset_of(&self, address: usize) -> Result<usize> {
+        match address.checked_sub(self.base) {
+            Some(offset) if offset < self.len() => Ok(offset),
+            _ => Err(Error::Unmapped(address)),
+        }
+    }
+}
+
+impl MemoryBackend for Buffer {
+    unsafe fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let offset = self.offset_of(address)?;
+        let bytes = self.bytes.borrow();
+        let count = buffer.len().min(bytes.len() - offset);
+        buffer[..count].copy_from_slice(&bytes[offset..offset + count]);
+        Ok(count)
+    }
+
+    unsafe fn write_bytes(&self, address: usize, bytes: &[u8]) -> Result<usize> {
+        if bytes.is_empty() {
+            return Ok(0);
+        }
+
+        let offset = self.offset_of(address)?;
+        let mut buffer = self.bytes.borrow_mut();
+        let count = bytes.len().min(buffer.len() - offset);
+        buffer[offset..offset + count].copy_from_slice(&bytes[..count]);
+        Ok(count)
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        let end = self
+            .base
+            .checked_add(self.len())
+            .ok_or(Error::AddressOverflow {
+                address: self.base,
+                offset: self.len(),
+            })?;
+
+        Ok(vec![MemoryRegion {
+            start: self.base,
+            end,
+            permissions: Permissions {
+                read: true,
+                write: true,
+                execute: false,
+                shared: false,
+            },
+            offset: 0,
+            device: (0, 0),
+            inode: 0,
+            pathname: None,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    #[test]
+    fn test_buffer_read_bytes() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![0x11, 0x22, 0x33, 0x44]);
+        let mut bytes = [0u8; 3];
+
+        unsafe {
+            assert_eq!(3, buffer.read_bytes(0x1001, &mut bytes).unwrap());
+            assert_eq!([0x22, 0x33, 0x44], bytes);
+
+            // reads running off the end of the buffer are partial
+            assert_eq!(1, buffer.read_bytes(0x1003, &mut bytes).unwrap());
+            assert_eq!(0x44, bytes[0]);
+
+            assert!(buffer.read_bytes(0x0fff, &mut bytes).is_err());
+            assert!(buffer.read_bytes(0x1004, &mut bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn test_buffer_write_bytes() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![0x11, 0x22, 0x33, 0x44]);
+
+        unsafe {
+            assert_eq!(2, buffer.write_bytes(0x1001, &[0xaa, 0xbb]).unwrap());
+            assert_eq!(1, buffer.write_bytes(0x1003, &[0xcc, 0xdd]).unwrap());
+            assert!(buffer.write_bytes(0x2000, &[0xee]).is_err());
+        }
+
+        assert_eq!(vec![0x11, 0xaa, 0xbb, 0xcc], buffer.into_inner());
+    }
+
+    #[test]
+    fn test_buffer_query_region() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![0; 0x10]);
+        let region = buffer.query_region(0x1008).unwrap().unwrap();
+
+        assert_eq!((0x1000, 0x1010), (region.start, region.end));
+        assert!(region.is_readable() && region.is_writable() && !region.is_executable());
+        assert_eq!(None, buffer.query_region(0x1010).unwrap());
+
+        let buffer = Buffer::new(usize::MAX - 1, vec![0; 4]);
+        assert!(matches!(
+            buffer.regions(),
+            Err(Error::AddressOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn test_local_read_write_bytes() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let n = 0xdeadbeefu32;
+        let address = std::ptr::addr_of!(n) as usize;
+        let mut bytes = [0u8; 4];
+
+        unsafe {
+            assert_eq!(4, Local.read_bytes(address, &mut bytes).unwrap());
+            assert_eq!(0xdeadbeefu32.to_ne_bytes(), bytes);
+
+            assert_eq!(
+                4,
+                Local
+                    .write_bytes(address, &0x12345678u32.to_ne_bytes())
+                    .unwrap()
+            );
+            assert_eq!(0x12345678, n);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_local_query_region() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let n = 0u32;
+        let region = Local
+            .query_region(std::ptr::addr_of!(n) as usize)
+            .unwrap()
+            .unwrap();
+        assert!(region.contains(std::ptr::addr_of!(n) as usize));
+        assert!(region.is_readable() && region.is_writable());
+
+        let region = Local
+            .query_region(test_local_query_region as *const () as usize)
+            .unwrap()
+            .unwrap();
+        assert!(region.is_readable() && !region.is_writable() && region.is_executable());
+
+        assert_eq!(None, Local.query_region(0).unwrap());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_checked_read_write_bytes() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let n = 0xdeadbeefu32;
+        let address = std::ptr::addr_of!(n) as usize;
+        let mut bytes = [0u8; 4];
+
+        unsafe {
+            assert_eq!(4, Checked.read_bytes(address, &mut bytes).unwrap());
+            assert_eq!(0xdeadbeefu32.to_ne_bytes(), bytes);
+
+            assert_eq!(
+                4,
+                Checked
+                    .write_bytes(address, &0x12345678u32.to_ne_bytes())
+                    .unwrap()
+            );
+            assert_eq!(0x12345678, n);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_checked_faults() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = [0u8; 4];
+
+        unsafe {
+            assert!(matches!(
+                Checked.read_bytes(8, &mut bytes),
+                Err(Error::Unmapped(8))
+            ));
+            assert!(matches!(
+                Checked.write_bytes(8, &bytes),
+                Err(Error::Unmapped(8))
+            ));
+
+            let code = test_checked_faults as *const () as usize;
+            assert!(matches!(
+                Checked.write_bytes(code, &bytes),
+                Err(Error::ProtectionFault(_))
+            ));
+
+            let page = libc::mmap(
+                std::ptr::null_mut(),
+                0x1000,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(libc::MAP_FAILED, page);
+            assert!(matches!(
+                Checked.read_bytes(page as usize, &mut bytes),
+                Err(Error::ProtectionFault(_))
+            ));
+            libc::munmap(page, 0x1000);
+        }
+    }
+}
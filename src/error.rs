@@ -0,0 +1,170 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidPattern {
+        position: usize,
+        reason: &'static str,
+    },
+    NullPointer,
+    NoOffsets,
+    AddressOverflow {
+        address: usize,
+        offset: usize,
+    },
+    InvalidPid(i32),
+    Unmapped(usize),
+    ProtectionFault(usize),
+    PartialRead {
+        address: usize,
+        expected: usize,
+        read: usize,
+    },
+    PartialWrite {
+        address: usize,
+        expected: usize,
+        written: usize,
+    },
+    ModuleNotFound(String),
+    PatternNotFound(String),
+    NoUniqueSignature(usize),
+    MatchCount {
+        pattern: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidPostOp(String),
+    InvalidDatabase(String),
+    InvalidSnapshot(String),
+    UnexpectedInstruction {
+        address: usize,
+        opcode: u8,
+    },
+    Cancelled,
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidPattern { position, reason } => {
+                write!(f, "invalid pattern at position {}: {}", position, reason)
+            }
+            Error::NullPointer => write!(f, "null pointer dereference"),
+            Error::NoOffsets => write!(f, "no offsets to resolve"),
+            Error::AddressOverflow { address, offset } => {
+                write!(f, "address {:#x} + {:#x} overflows", address, offset)
+            }
+            Error::InvalidPid(pid) => write!(f, "invalid process id {}", pid),
+            Error::Unmapped(address) => write!(f, "address {:#x} is not mapped", address),
+            Error::ProtectionFault(address) => {
+                write!(f, "access to {:#x} violates page protection", address)
+            }
+            Error::PartialRead {
+                address,
+                expected,
+                read,
+            } => write!(f, "read {} of {} bytes at {:#x}", read, expected, address),
+            Error::PartialWrite {
+                address,
+                expected,
+                written,
+            } => write!(
+                f,
+                "wrote {} of {} bytes at {:#x}",
+                written, expected, address
+            ),
+            Error::ModuleNotFound(name) => write!(f, "module {} is not loaded", name),
+            Error::PatternNotFound(pattern) => write!(f, "pattern {} was not found", pattern),
+            Error::NoUniqueSignature(address) => {
+                write!(f, "no unique signature found for {:#x}", address)
+            }
+            Error::MatchCount {
+                pattern,
+                expected,
+                found,
+            } => write!(
+                f,
+                "pattern {} matched {} times, expected {}",
+                pattern, found, expected
+            ),
+            Error::InvalidPostOp(op) => write!(f, "invalid post-op {:?}", op),
+            Error::InvalidDatabase(reason) => write!(f, "invalid signature database: {}", reason),
+            Error::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+            Error::UnexpectedInstruction { address, opcode } => write!(
+                f,
+                "expected a call or jmp at {:#x}, found opcode {:#04x}",
+                address, opcode
+            ),
+            Error::Cancelled => write!(f, "operation was cancelled"),
+            Error::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    #[test]
+    fn test_error_display() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        assert_eq!(
+            "invalid pattern at position 3: invalid hex digit",
+            Error::InvalidPattern {
+                position: 3,
+                reason: "invalid hex digit"
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "address 0xffffffffffffffff + 0x8 overflows",
+            Error::AddressOverflow {
+                address: usize::MAX,
+                offset: 8
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "address 0x1000 is not mapped",
+            Error::Unmapped(0x1000).to_string()
+        );
+        assert_eq!(
+            "read 2 of 4 bytes at 0x1000",
+            Error::PartialRead {
+                address: 0x1000,
+                expected: 4,
+                read: 2
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_error_from_io() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let error = Error::from(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(matches!(error, Error::Io(_)));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+}
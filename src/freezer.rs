@@ -0,0 +1,376 @@
+use crate::error::Result;
+use crate::memory_edit::MemoryEdit;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// Shorter intervals, zero included, are raised to this so the thread never
+// spins on the entries lock.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FreezeId(u64);
+
+struct Entry {
+    edit: Box<dyn MemoryEdit + Send>,
+    enabled: bool,
+}
+
+struct Shared {
+    // each entry has a lock of its own, so the thread can apply them without
+    // holding up freeze and unfreeze on the others
+    entries: Mutex<Vec<(FreezeId, Arc<Mutex<Entry>>)>>,
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+// Keeps memory edits in place against a target that keeps overwriting them, by
+// applying every enabled edit again each interval on a background thread.
+// Dropping the freezer stops the thread and reverts whatever is still frozen.
+pub struct Freezer {
+    shared: Arc<Shared>,
+    interval: Duration,
+    next_id: u64,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Freezer {
+    pub fn new(interval: Duration) -> Self {
+        let interval = interval.max(MIN_INTERVAL);
+        let shared = Arc::new(Shared {
+            entries: Mutex::new(vec![]),
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            std::thread::spawn(move || loop {
+                let entries: Vec<_> = shared
+                    .entries
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(_, entry)| entry.clone())
+                    .collect();
+
+                for entry in entries {
+                    let mut entry = entry.lock().unwrap();
+                    if entry.enabled {
+                        // a write failing now may well succeed next time
+                        let _ = entry.edit.try_edit();
+                    }
+                }
+
+                let stopped = shared.stopped.lock().unwrap();
+                let (stopped, _) = shared
+                    .wake
+                    .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                    .unwrap();
+                if *stopped {
+                    break;
+                }
+            })
+        };
+
+        Freezer {
+            shared,
+            interval,
+            next_id: 0,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    // Applies `edit` right away and keeps applying it from then on.
+    pub fn freeze<E: MemoryEdit + Send + 'static>(&mut self, mut edit: E) -> Result<FreezeId> {
+        edit.try_edit()?;
+
+        let id = FreezeId(self.next_id);
+        self.next_id += 1;
+        self.shared.entries.lock().unwrap().push((
+            id,
+            Arc::new(Mutex::new(Entry {
+                edit: Box::new(edit),
+                enabled: true,
+            })),
+        ));
+
+        Ok(id)
+    }
+
+    // Stops applying the edit and reverts it. Ok(false) if it was not frozen.
+    pub fn unfreeze(&mut self, id: FreezeId) -> Result<bool> {
+        let removed = {
+            let mut entries = self.shared.entries.lock().unwrap();
+            entries
+                .iter()
+                .position(|(entry_id, _)| *entry_id == id)
+                .map(|index| entries.remove(index).1)
+        };
+
+        match removed {
+            Some(entry) => {
+                // the thread may still hold the entry from before it was removed,
+                // disabled it is not applied again
+                let mut entry = entry.lock().unwrap();
+                entry.enabled = false;
+                entry.edit.try_revert()?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn entry(&self, id: FreezeId) -> Option<Arc<Mutex<Entry>>> {
+        self.shared
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(entry_id, _)| *entry_id == id)
+            .map(|(_, entry)| entry.clone())
+    }
+
+    // A disabled edit stays frozen but is left alone, as the target last wrote
+    // it, until enabled again. False if it is not frozen.
+    pub fn set_enabled(&mut self, id: FreezeId, enabled: bool) -> bool {
+        match self.entry(id) {
+            Some(entry) => {
+                entry.lock().unwrap().enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_enabled(&self, id: FreezeId) -> Option<bool> {
+        self.entry(id).map(|entry| entry.lock().unwrap().enabled)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for Freezer {
+    fn drop(&mut self) {
+        *self.shared.stopped.lock().unwrap() = true;
+        self.shared.wake.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        for (_, entry) in self.shared.entries.lock().unwrap().drain(..) {
+            let _ = entry.lock().unwrap().edit.try_revert();
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::address::Address;
+    use crate::backend::{Buffer, MemoryBackend};
+    use crate::memory_edit::{MemoryDataEdit, MemoryPatch};
+    use crate::regions::MemoryRegion;
+    use std::time::Instant;
+
+    // A Buffer the test and the freezer thread can both reach, one at a time.
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Buffer>>);
+
+    impl SharedBuffer {
+        fn read_u32(&self, address: usize) -> u32 {
+            let mut bytes = [0; 4];
+            unsafe { self.read_bytes(address, &mut bytes).unwrap() };
+            u32::from_ne_bytes(bytes)
+        }
+
+        fn write_u32(&self, address: usize, value: u32) {
+            unsafe { self.write_bytes(address, &value.to_ne_bytes()).unwrap() };
+        }
+    }
+
+    impl MemoryBackend for SharedBuffer {
+        unsafe fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+            self.0.lock().unwrap().read_bytes(address, buffer)
+        }
+
+        unsafe fn write_bytes(&self, address: usize, bytes: &[u8]) -> Result<usize> {
+            self.0.lock().unwrap().write_bytes(address, bytes)
+        }
+
+        fn regions(&self) -> Result<Vec<MemoryRegion>> {
+            self.0.lock().unwrap().regions()
+        }
+    }
+
+    fn wait_for(condition: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        false
+    }
+
+    #[test]
+    fn test_freezer() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let memory = SharedBuffer(Arc::new(Mutex::new(Buffer::new(0x1000, vec![0; 8]))));
+        let (value, bytes) = (0x1000, 0x1004);
+        memory.write_u32(value, 100);
+        memory.write_u32(bytes, 0xdeadbeef);
+        let mut freezer = Freezer::new(Duration::from_millis(1));
+
+        let frozen = freezer
+            .freeze(MemoryDataEdit::<u32, _>::new(
+                Address::with_backend(memory.clone(), value as *mut u8),
+                999,
+            ))
+            .unwrap();
+        let patched = freezer
+            .freeze(MemoryPatch::new(
+                Address::with_backend(memory.clone(), bytes as *mut u8),
+                vec![0x90, 0x90],
+            ))
+            .unwrap();
+        assert_eq!(2, freezer.len());
+        assert_eq!(999, memory.read_u32(value));
+
+        memory.write_u32(value, 5);
+        assert!(wait_for(|| memory.read_u32(value) == 999));
+
+        assert!(freezer.set_enabled(frozen, false));
+        assert_eq!(Some(false), freezer.is_enabled(frozen));
+        memory.write_u32(value, 5);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(5, memory.read_u32(value));
+
+        assert!(freezer.set_enabled(frozen, true));
+        assert!(wait_for(|| memory.read_u32(value) == 999));
+
+        assert!(freezer.unfreeze(frozen).unwrap());
+        assert_eq!(100, memory.read_u32(value));
+        assert!(!freezer.unfreeze(frozen).unwrap());
+        assert!(!freezer.set_enabled(frozen, true));
+        assert_eq!(None, freezer.is_enabled(frozen));
+
+        memory.write_u32(bytes, 0);
+        assert!(wait_for(|| memory.read_u32(bytes) == 0x9090));
+
+        assert_eq!(Some(true), freezer.is_enabled(patched));
+        drop(freezer);
+        // only the two patched bytes are restored
+        assert_eq!(0xbeef, memory.read_u32(bytes));
+    }
+
+    // Writes a new count on every apply, so the freezer thread makes the page
+    // writable each time instead of finding the edit in place.
+    #[cfg(target_os = "linux")]
+    struct Counter {
+        ptr: Address,
+        count: u32,
+    }
+
+    #[cfg(target_os = "linux")]
+    impl MemoryEdit for Counter {
+        fn try_edit(&mut self) -> Result<()> {
+            self.count += 1;
+            let count = self.count;
+            unsafe {
+                self.ptr
+                    .try_with_write_access(4, |ptr| ptr.try_write(count))
+            }
+        }
+
+        fn try_revert(&mut self) -> Result<()> {
+            unsafe { self.ptr.try_with_write_access(4, |ptr| ptr.try_write(0u32)) }
+        }
+
+        fn is_applied(&self) -> bool {
+            true
+        }
+
+        fn try_status(&self) -> Result<crate::memory_edit::EditStatus> {
+            Ok(crate::memory_edit::EditStatus::Applied)
+        }
+    }
+
+    // The freezer thread and a patch on the same read-only page each make it
+    // writable for their write, neither may take write access from the other.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_freezer_protected_page() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        unsafe {
+            let page_size = crate::protection::page_size();
+            let page = libc::mmap(
+                std::ptr::null_mut(),
+                page_size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(libc::MAP_FAILED, page);
+            let page = page as usize;
+            let read_only = crate::protection::query(page).unwrap();
+
+            let mut freezer = Freezer::new(Duration::ZERO);
+            let frozen = freezer
+                .freeze(Counter {
+                    ptr: Address::new(page as *mut u8),
+                    count: 0,
+                })
+                .unwrap();
+
+            let mut patch = MemoryPatch::new(Address::new((page + 0x100) as *mut u8), vec![0x90]);
+            for _ in 0..5000 {
+                patch.try_edit().unwrap();
+                patch.try_revert().unwrap();
+            }
+
+            // no writes from the thread once disabled
+            assert!(freezer.set_enabled(frozen, false));
+            assert!(*(page as *const u32) > 1);
+            assert_eq!(0, *((page + 0x100) as *const u8));
+            assert_eq!(read_only, crate::protection::query(page).unwrap());
+
+            drop(freezer);
+            assert_eq!(0, *(page as *const u32));
+            assert_eq!(read_only, crate::protection::query(page).unwrap());
+
+            libc::munmap(page as *mut _, page_size);
+        }
+    }
+
+    #[test]
+    fn test_freezer_shutdown() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        // stopping does not wait out the interval
+        let start = Instant::now();
+        let freezer = Freezer::new(Duration::from_secs(60));
+        assert!(freezer.is_empty());
+        assert_eq!(Duration::from_secs(60), freezer.interval());
+        drop(freezer);
+        assert!(start.elapsed() < Duration::from_secs(30));
+
+        let freezer = Freezer::new(Duration::ZERO);
+        assert_eq!(MIN_INTERVAL, freezer.interval());
+    }
+}
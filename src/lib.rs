@@ -1,4 +1,25 @@
 pub mod address;
+pub mod backend;
+pub mod error;
+pub mod freezer;
 pub mod memory_edit;
+pub mod module;
+pub mod parallel;
+pub mod pattern;
+pub mod pattern_format;
 pub mod pattern_match;
+pub mod pattern_set;
+pub mod post_op;
+#[cfg(target_os = "linux")]
+pub mod process;
+pub mod protection;
+pub mod regions;
+pub mod scanner;
+pub mod signature;
+pub mod signature_database;
+pub mod snapshot;
+pub mod string_scan;
 pub mod util;
+pub mod value_scan;
+
+pub use error::{Error, Result};
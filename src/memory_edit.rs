@@ -1,77 +1,270 @@
 use crate::address::Address;
+use crate::backend::{Local, MemoryBackend};
+use crate::error::{Error, Result};
+use std::marker::PhantomData;
 
+// What the edited memory holds now, compared to what the edit last put there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EditStatus {
+    // applied, and the replacement is still in place
+    Applied,
+    // not applied, and the original is still in place
+    Reverted,
+    // something else wrote there since
+    Tampered,
+}
+
+// Both directions are idempotent: try_edit only writes when the replacement is
+// not in place already, which also puts it back after tampering, and try_revert
+// only writes when the edit is applied.
 pub trait MemoryEdit {
-    fn edit(&mut self);
-    fn revert(&mut self);
+    fn try_edit(&mut self) -> Result<()>;
+    fn try_revert(&mut self) -> Result<()>;
+
+    fn is_applied(&self) -> bool;
+
+    // Reads the edited memory to tell whether it still holds what it should.
+    fn try_status(&self) -> Result<EditStatus>;
+
+    fn status(&self) -> EditStatus {
+        self.try_status().expect("failed to read memory")
+    }
+
+    fn edit(&mut self) {
+        self.try_edit().expect("failed to apply memory edit");
+    }
+
+    fn revert(&mut self) {
+        self.try_revert().expect("failed to revert memory edit");
+    }
+
+    // Applies the edit for as long as the returned guard lives, see ScopedEdit.
+    fn try_scoped(self) -> Result<ScopedEdit<Self>>
+    where
+        Self: Sized,
+    {
+        ScopedEdit::try_new(self)
+    }
+
+    fn scoped(self) -> ScopedEdit<Self>
+    where
+        Self: Sized,
+    {
+        self.try_scoped().expect("failed to apply memory edit")
+    }
+}
+
+// An applied edit that is reverted when the guard is dropped, unwinding from a
+// panic included, so a failing tool does not leave its target patched. commit
+// and leak keep the edit in place instead.
+pub struct ScopedEdit<E: MemoryEdit> {
+    edit: Option<E>,
+}
+
+pub type ScopedPatch<B = Local> = ScopedEdit<MemoryPatch<B>>;
+pub type ScopedDataEdit<T, B = Local> = ScopedEdit<MemoryDataEdit<T, B>>;
+
+impl<E: MemoryEdit> ScopedEdit<E> {
+    pub fn try_new(mut edit: E) -> Result<Self> {
+        edit.try_edit()?;
+
+        Ok(ScopedEdit { edit: Some(edit) })
+    }
+
+    pub fn new(edit: E) -> Self {
+        ScopedEdit::try_new(edit).expect("failed to apply memory edit")
+    }
+
+    // Whether the edit is still to be reverted on drop.
+    pub fn is_active(&self) -> bool {
+        self.get().is_applied()
+    }
+
+    pub fn get(&self) -> &E {
+        self.edit.as_ref().unwrap()
+    }
+
+    // Reverts early, dropping the guard afterwards does nothing.
+    pub fn try_revert(&mut self) -> Result<()> {
+        self.edit.as_mut().unwrap().try_revert()
+    }
+
+    // Applies the edit again after try_revert.
+    pub fn try_reapply(&mut self) -> Result<()> {
+        self.edit.as_mut().unwrap().try_edit()
+    }
+
+    // Keeps the edit in place and hands it back, to be reverted by hand later.
+    pub fn commit(mut self) -> E {
+        self.edit.take().unwrap()
+    }
+
+    // Keeps the edit in place for good.
+    pub fn leak(self) {
+        self.commit();
+    }
+}
+
+impl<E: MemoryEdit> Drop for ScopedEdit<E> {
+    fn drop(&mut self) {
+        if let Some(edit) = self.edit.as_mut() {
+            // nothing to be done about a failure while dropping
+            let _ = edit.try_revert();
+        }
+    }
 }
 
-pub struct MemoryPatch {
-    ptr: Address,
+pub struct MemoryPatch<B: MemoryBackend = Local> {
+    ptr: Address<B>,
     replace_bytes: Vec<u8>,
     retain_bytes: Vec<u8>,
+    applied: bool,
+}
+
+// A MemoryPatch of the bytes of a T, statuses compare those bytes the same way.
+// The T is only turned into bytes, never read back from memory.
+pub struct MemoryDataEdit<T, B: MemoryBackend = Local> {
+    patch: MemoryPatch<B>,
+    data: PhantomData<T>,
 }
 
-pub struct MemoryDataEdit<T> {
-    ptr: Address,
-    replace_data: T,
-    retain_data: T,
+// The `size` bytes at ptr as they are now.
+fn read_current<B: MemoryBackend>(ptr: &Address<B>, size: usize) -> Result<Vec<u8>> {
+    let address = ptr.ptr() as usize;
+    let mut current = vec![0u8; size];
+    let read = unsafe { ptr.backend().read_bytes(address, &mut current)? };
+
+    if read != size {
+        return Err(Error::PartialRead {
+            address,
+            expected: size,
+            read,
+        });
+    }
+
+    Ok(current)
 }
 
-impl MemoryPatch {
-    pub fn new(ptr: Address, bytes: Vec<u8>) -> Self {
+fn status_of(applied: bool, current: &[u8], replace: &[u8], retain: &[u8]) -> EditStatus {
+    match applied {
+        true if current == replace => EditStatus::Applied,
+        false if current == retain => EditStatus::Reverted,
+        _ => EditStatus::Tampered,
+    }
+}
+
+impl<B: MemoryBackend> MemoryPatch<B> {
+    pub fn new(ptr: Address<B>, bytes: Vec<u8>) -> Self {
+        MemoryPatch::try_new(ptr, bytes).expect("failed to read memory")
+    }
+
+    pub fn try_new(ptr: Address<B>, bytes: Vec<u8>) -> Result<Self> {
         let mut memory_patch = MemoryPatch {
-            ptr: ptr,
+            ptr,
             replace_bytes: bytes.clone(),
             retain_bytes: vec![],
+            applied: false,
         };
 
         unsafe {
-            memory_patch.retain_bytes = memory_patch.ptr.read_memory(bytes.len());
+            memory_patch.retain_bytes = memory_patch.ptr.try_read_memory(bytes.len())?;
         }
 
-        memory_patch
+        Ok(memory_patch)
     }
 }
 
-impl MemoryEdit for MemoryPatch {
-    fn edit(&mut self) {
-        unsafe { self.ptr.write_memory(&self.replace_bytes) }
-    }
+impl<B: MemoryBackend> MemoryEdit for MemoryPatch<B> {
+    fn try_edit(&mut self) -> Result<()> {
+        if self.try_status()? == EditStatus::Applied {
+            return Ok(());
+        }
 
-    fn revert(&mut self) {
-        unsafe { self.ptr.write_memory(&self.retain_bytes) }
+        unsafe {
+            self.ptr
+                .try_with_write_access(self.replace_bytes.len(), |ptr| {
+                    ptr.try_write_memory(&self.replace_bytes)
+                })?;
+        }
+        self.applied = true;
+
+        Ok(())
     }
-}
 
-impl<T: Clone> MemoryDataEdit<T> {
-    pub fn new(ptr: Address, data: T) -> Self {
-        let mut memory_data_edit = MemoryDataEdit::<T> {
-            ptr: ptr,
-            replace_data: data.clone(),
-            retain_data: data.clone(),
-        };
+    fn try_revert(&mut self) -> Result<()> {
+        if !self.applied {
+            return Ok(());
+        }
 
         unsafe {
-            memory_data_edit.retain_data = memory_data_edit.ptr.read::<T>();
+            self.ptr
+                .try_with_write_access(self.retain_bytes.len(), |ptr| {
+                    ptr.try_write_memory(&self.retain_bytes)
+                })?;
         }
+        self.applied = false;
+
+        Ok(())
+    }
+
+    fn is_applied(&self) -> bool {
+        self.applied
+    }
 
-        memory_data_edit
+    fn try_status(&self) -> Result<EditStatus> {
+        Ok(status_of(
+            self.applied,
+            &read_current(&self.ptr, self.replace_bytes.len())?,
+            &self.replace_bytes,
+            &self.retain_bytes,
+        ))
     }
 }
 
-impl<T: Clone> MemoryEdit for MemoryDataEdit<T> {
-    fn edit(&mut self) {
-        unsafe { self.ptr.write::<T>(self.replace_data.clone()) }
+impl<T: Copy, B: MemoryBackend> MemoryDataEdit<T, B> {
+    pub fn new(ptr: Address<B>, data: T) -> Self {
+        MemoryDataEdit::try_new(ptr, data).expect("failed to read memory")
     }
 
-    fn revert(&mut self) {
-        unsafe { self.ptr.write::<T>(self.retain_data.clone()) }
+    pub fn try_new(ptr: Address<B>, data: T) -> Result<Self> {
+        // the bytes Address::try_write would write for data
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                std::ptr::addr_of!(data) as *const u8,
+                std::mem::size_of::<T>(),
+            )
+        };
+
+        Ok(MemoryDataEdit {
+            patch: MemoryPatch::try_new(ptr, bytes.to_vec())?,
+            data: PhantomData,
+        })
+    }
+}
+
+impl<T: Copy, B: MemoryBackend> MemoryEdit for MemoryDataEdit<T, B> {
+    fn try_edit(&mut self) -> Result<()> {
+        self.patch.try_edit()
+    }
+
+    fn try_revert(&mut self) -> Result<()> {
+        self.patch.try_revert()
+    }
+
+    fn is_applied(&self) -> bool {
+        self.patch.is_applied()
+    }
+
+    fn try_status(&self) -> Result<EditStatus> {
+        self.patch.try_status()
     }
 }
 
 #[cfg(test)]
 mod unit_test {
     use super::*;
+    use crate::backend::Buffer;
+    use crate::error::Error;
 
     #[test]
     fn test_memory_patch_edit() {
@@ -142,4 +335,228 @@ mod unit_test {
         data_edit.revert();
         assert_eq!(0xdeadbeef, n);
     }
+
+    #[test]
+    fn test_memory_edit_buffer_backend() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![0xef, 0xbe, 0xad, 0xde, 0x00, 0x00]);
+
+        let mut patch = MemoryPatch::new(
+            Address::with_backend(&buffer, 0x1000 as *mut u8),
+            vec![0x90, 0x90],
+        );
+        let mut data_edit = MemoryDataEdit::<u16, _>::new(
+            Address::with_backend(&buffer, 0x1004 as *mut u8),
+            0x1234,
+        );
+
+        patch.edit();
+        data_edit.edit();
+        assert_eq!(vec![0x90, 0x90, 0xad, 0xde, 0x34, 0x12], buffer.to_vec());
+
+        patch.revert();
+        data_edit.revert();
+        assert_eq!(vec![0xef, 0xbe, 0xad, 0xde, 0x00, 0x00], buffer.to_vec());
+    }
+
+    #[test]
+    fn test_memory_edit_try_new() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![0xef, 0xbe, 0xad, 0xde]);
+
+        assert!(matches!(
+            MemoryPatch::try_new(
+                Address::with_backend(&buffer, 0x1002 as *mut u8),
+                vec![0x90, 0x90, 0x90]
+            ),
+            Err(Error::PartialRead {
+                address: 0x1002,
+                expected: 3,
+                read: 2
+            })
+        ));
+        assert!(matches!(
+            MemoryDataEdit::<u32, _>::try_new(Address::with_backend(&buffer, 0x2000 as *mut u8), 0),
+            Err(Error::Unmapped(0x2000))
+        ));
+
+        let mut patch = MemoryPatch::try_new(
+            Address::with_backend(&buffer, 0x1000 as *mut u8),
+            vec![0x90],
+        )
+        .unwrap();
+        patch.try_edit().unwrap();
+        assert_eq!(vec![0x90, 0xbe, 0xad, 0xde], buffer.to_vec());
+        patch.try_revert().unwrap();
+        assert_eq!(vec![0xef, 0xbe, 0xad, 0xde], buffer.to_vec());
+    }
+
+    #[test]
+    fn test_memory_edit_status() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![0xef, 0xbe, 0xad, 0xde, 0x00, 0x00]);
+        let mut target = Address::with_backend(&buffer, 0x1000 as *mut u8);
+
+        let mut patch = MemoryPatch::new(
+            Address::with_backend(&buffer, 0x1000 as *mut u8),
+            vec![0x90, 0x90],
+        );
+        assert!(!patch.is_applied());
+        assert_eq!(EditStatus::Reverted, patch.status());
+
+        // reverting an edit that was never applied writes nothing
+        unsafe { target.write_memory(&vec![0x11]) };
+        patch.revert();
+        assert_eq!(vec![0x11, 0xbe, 0xad, 0xde, 0x00, 0x00], buffer.to_vec());
+        assert_eq!(EditStatus::Tampered, patch.status());
+        unsafe { target.write_memory(&vec![0xef]) };
+
+        patch.edit();
+        patch.edit();
+        assert!(patch.is_applied());
+        assert_eq!(EditStatus::Applied, patch.status());
+
+        // applying again puts the replacement back
+        unsafe { target.write_memory(&vec![0xcc]) };
+        assert_eq!(EditStatus::Tampered, patch.status());
+        patch.edit();
+        assert_eq!(EditStatus::Applied, patch.status());
+        assert_eq!(vec![0x90, 0x90, 0xad, 0xde, 0x00, 0x00], buffer.to_vec());
+
+        patch.revert();
+        patch.revert();
+        assert_eq!(EditStatus::Reverted, patch.status());
+        assert_eq!(vec![0xef, 0xbe, 0xad, 0xde, 0x00, 0x00], buffer.to_vec());
+
+        let mut data_edit = MemoryDataEdit::<u16, _>::new(
+            Address::with_backend(&buffer, 0x1004 as *mut u8),
+            0x1234,
+        );
+        data_edit.edit();
+        assert_eq!(EditStatus::Applied, data_edit.status());
+        unsafe { Address::with_backend(&buffer, 0x1004 as *mut u8).write::<u16>(0x1235) };
+        assert_eq!(EditStatus::Tampered, data_edit.status());
+        data_edit.revert();
+        assert_eq!(EditStatus::Reverted, data_edit.status());
+
+        // bytes are compared, not values: a NaN stays in place, and -0.0 is not
+        // the 0.0 that was written
+        let mut float_edit = MemoryDataEdit::<f32, _>::new(
+            Address::with_backend(&buffer, 0x1000 as *mut u8),
+            f32::NAN,
+        );
+        float_edit.edit();
+        assert_eq!(EditStatus::Applied, float_edit.status());
+        float_edit.revert();
+        assert_eq!(EditStatus::Reverted, float_edit.status());
+
+        let mut float_edit =
+            MemoryDataEdit::<f32, _>::new(Address::with_backend(&buffer, 0x1000 as *mut u8), 0.0);
+        float_edit.edit();
+        unsafe { Address::with_backend(&buffer, 0x1000 as *mut u8).write::<f32>(-0.0) };
+        assert_eq!(EditStatus::Tampered, float_edit.status());
+        float_edit.edit();
+        assert_eq!(EditStatus::Applied, float_edit.status());
+        float_edit.revert();
+        assert_eq!(EditStatus::Reverted, float_edit.status());
+        assert_eq!(vec![0xef, 0xbe, 0xad, 0xde, 0x00, 0x00], buffer.to_vec());
+    }
+
+    #[test]
+    fn test_scoped_edit() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![0xef, 0xbe, 0xad, 0xde]);
+        let patch = || {
+            MemoryPatch::new(
+                Address::with_backend(&buffer, 0x1000 as *mut u8),
+                vec![0x90, 0x90],
+            )
+        };
+
+        {
+            let mut scoped = patch().scoped();
+            assert!(scoped.is_active());
+            assert_eq!(vec![0x90, 0x90, 0xad, 0xde], buffer.to_vec());
+
+            scoped.try_revert().unwrap();
+            assert!(!scoped.is_active());
+            assert_eq!(vec![0xef, 0xbe, 0xad, 0xde], buffer.to_vec());
+
+            scoped.try_reapply().unwrap();
+            assert_eq!(vec![0x90, 0x90, 0xad, 0xde], buffer.to_vec());
+        }
+        assert_eq!(vec![0xef, 0xbe, 0xad, 0xde], buffer.to_vec());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _scoped: ScopedPatch<_> = ScopedEdit::new(patch());
+            assert_eq!(vec![0x90, 0x90, 0xad, 0xde], buffer.to_vec());
+            panic!("tool failed while patched");
+        }));
+        assert!(result.is_err());
+        assert_eq!(vec![0xef, 0xbe, 0xad, 0xde], buffer.to_vec());
+
+        let mut committed = patch().scoped().commit();
+        assert_eq!(vec![0x90, 0x90, 0xad, 0xde], buffer.to_vec());
+        committed.revert();
+        assert_eq!(vec![0xef, 0xbe, 0xad, 0xde], buffer.to_vec());
+
+        let data_edit: ScopedDataEdit<u16, _> =
+            MemoryDataEdit::new(Address::with_backend(&buffer, 0x1002 as *mut u8), 0x1234).scoped();
+        data_edit.leak();
+        assert_eq!(vec![0xef, 0xbe, 0x34, 0x12], buffer.to_vec());
+
+        // the guard reverts to what was there when the edit was made
+        {
+            let scoped =
+                MemoryDataEdit::<u32, _>::new(Address::with_backend(&buffer, 0x1000 as *mut u8), 0)
+                    .try_scoped()
+                    .unwrap();
+            assert_eq!(
+                0x1234beefu32.to_ne_bytes().to_vec(),
+                scoped.get().patch.retain_bytes
+            );
+            assert_eq!(vec![0, 0, 0, 0], buffer.to_vec());
+        }
+        assert_eq!(vec![0xef, 0xbe, 0x34, 0x12], buffer.to_vec());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_memory_edit_read_only_page() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        unsafe {
+            let page = libc::mmap(
+                std::ptr::null_mut(),
+                0x1000,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(libc::MAP_FAILED, page);
+            *(page as *mut u32) = 0xdeadbeef;
+            assert_eq!(0, libc::mprotect(page, 0x1000, libc::PROT_READ));
+
+            let mut patch = MemoryPatch::new(Address::new(page as *mut u8), vec![0x90, 0x90]);
+            let mut data_edit =
+                MemoryDataEdit::<u16>::new(Address::new((page as *mut u8).add(2)), 0x1234);
+
+            patch.edit();
+            data_edit.edit();
+            assert_eq!(0x12349090, *(page as *const u32));
+            assert!(!crate::protection::query(page as usize).unwrap().write);
+
+            patch.revert();
+            data_edit.revert();
+            assert_eq!(0xdeadbeef, *(page as *const u32));
+            assert!(!crate::protection::query(page as usize).unwrap().write);
+
+            libc::munmap(page, 0x1000);
+        }
+    }
 }
@@ -0,0 +1,237 @@
+use crate::address::Address;
+use crate::backend::MemoryBackend;
+use crate::error::{Error, Result};
+#[cfg(target_os = "linux")]
+use crate::process::Process;
+use crate::regions::MemoryRegion;
+use std::ops::Range;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Module {
+    pub name: String,
+    pub path: String,
+    pub base: usize,
+    pub size: usize,
+    pub segments: Vec<MemoryRegion>,
+}
+
+impl Module {
+    pub fn end(&self) -> usize {
+        self.base + self.size
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.base..self.end()
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        self.base <= address && address < self.end()
+    }
+
+    pub fn offset_of(&self, address: usize) -> Option<usize> {
+        if self.contains(address) {
+            Some(address - self.base)
+        } else {
+            None
+        }
+    }
+
+    // The readable parts of the module, adjacent segments merged so a match may
+    // run from one into the next, and clipped to base..end. A module without
+    // segments, e.g. one put together by hand, is taken to be readable whole.
+    pub fn readable_ranges(&self) -> Vec<Range<usize>> {
+        if self.segments.is_empty() {
+            return vec![self.range()];
+        }
+
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for segment in self.segments.iter().filter(|segment| segment.is_readable()) {
+            let (start, end) = (segment.start.max(self.base), segment.end.min(self.end()));
+            if start >= end {
+                continue;
+            }
+
+            match ranges.last_mut() {
+                Some(range) if range.end == start => range.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+
+        ranges
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.path == name
+    }
+
+    pub fn address(&self, offset: usize) -> Address {
+        Address::new((self.base + offset) as *mut u8)
+    }
+
+    pub fn address_with_backend<B: MemoryBackend>(&self, backend: B, offset: usize) -> Address<B> {
+        Address::with_backend(backend, (self.base + offset) as *mut u8)
+    }
+}
+
+// The kernel's mark on the maps and /proc/pid/exe paths of a file that was
+// deleted, or replaced by e.g. a rebuild, after it was mapped.
+fn strip_deleted(path: &str) -> &str {
+    path.strip_suffix(" (deleted)").unwrap_or(path)
+}
+
+// Groups file backed mappings by the file they map, in order of first appearance.
+fn modules_from_regions(regions: Vec<MemoryRegion>) -> Vec<Module> {
+    let mut modules: Vec<Module> = vec![];
+
+    for region in regions.into_iter().filter(|region| region.is_file()) {
+        let path = match region.pathname.as_deref() {
+            Some(path) => strip_deleted(path).to_string(),
+            None => continue,
+        };
+
+        match modules.iter_mut().find(|module| {
+            module.path == path
+                && module.segments[0].inode == region.inode
+                && module.segments[0].device == region.device
+        }) {
+            Some(module) => {
+                let end = module.end().max(region.end);
+                module.base = module.base.min(region.start);
+                module.size = end - module.base;
+                module.segments.push(region);
+            }
+            None => modules.push(Module {
+                name: Path::new(&path)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(&path)
+                    .to_string(),
+                path,
+                base: region.start,
+                size: region.size(),
+                segments: vec![region],
+            }),
+        }
+    }
+
+    modules
+}
+
+pub fn list<B: MemoryBackend>(backend: &B) -> Result<Vec<Module>> {
+    Ok(modules_from_regions(backend.regions()?))
+}
+
+// `name` is either the file name of the module, e.g. "libc.so.6", or its full path.
+pub fn find<B: MemoryBackend>(backend: &B, name: &str) -> Result<Module> {
+    list(backend)?
+        .into_iter()
+        .find(|module| module.matches(name))
+        .ok_or_else(|| Error::ModuleNotFound(name.to_string()))
+}
+
+#[cfg(target_os = "linux")]
+pub fn main_module(process: &Process) -> Result<Module> {
+    let exe = std::fs::read_link(format!("/proc/{}/exe", process.pid()))?;
+    let exe = exe.to_string_lossy();
+
+    find(process, strip_deleted(&exe))
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::regions::parse_maps;
+
+    const MAPS: &str = "\
+55bd5de47000-55bd5de49000 r--p 00000000 fe:00 317563                     /usr/bin/cat
+55bd5de49000-55bd5de4e000 r-xp 00002000 fe:00 317563                     /usr/bin/cat
+55bd5de52000-55bd5de53000 rw-p 0000a000 fe:00 317563                     /usr/bin/cat
+55bd83f95000-55bd83fb6000 rw-p 00000000 00:00 0                          [heap]
+7fdc6cf98000-7fdc6cfbe000 r--p 00000000 fe:00 395379                     /usr/lib/x86_64-linux-gnu/libc.so.6
+7fdc6cfbe000-7fdc6d114000 r-xp 00026000 fe:00 395379                     /usr/lib/x86_64-linux-gnu/libc.so.6
+7fdc6d16d000-7fdc6d17a000 rw-p 00000000 00:00 0
+7ffd90a64000-7ffd90a85000 rw-p 00000000 00:00 0                          [stack]
+";
+
+    #[test]
+    fn test_module_from_regions() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let modules = modules_from_regions(parse_maps(MAPS));
+        assert_eq!(2, modules.len());
+
+        assert_eq!("cat", modules[0].name);
+        assert_eq!("/usr/bin/cat", modules[0].path);
+        assert_eq!(0x55bd5de47000, modules[0].base);
+        assert_eq!(0xc000, modules[0].size);
+        assert_eq!(3, modules[0].segments.len());
+        assert_eq!(
+            vec![
+                0x55bd5de47000..0x55bd5de4e000,
+                0x55bd5de52000..0x55bd5de53000
+            ],
+            modules[0].readable_ranges()
+        );
+
+        assert_eq!("libc.so.6", modules[1].name);
+        assert_eq!(0x7fdc6cf98000..0x7fdc6d114000, modules[1].range());
+        assert_eq!(Some(0x26000), modules[1].offset_of(0x7fdc6cfbe000));
+        assert_eq!(None, modules[1].offset_of(0x7fdc6d114000));
+        assert!(modules[1].matches("/usr/lib/x86_64-linux-gnu/libc.so.6"));
+        assert_eq!(0x7fdc6cfbe000 as *mut u8, modules[1].address(0x26000).ptr());
+
+        let modules = modules_from_regions(parse_maps(
+            "\
+55bd5de47000-55bd5de49000 r--p 00000000 fe:00 317563                     /opt/game/game (deleted)
+55bd5de49000-55bd5de4e000 r-xp 00002000 fe:00 317563                     /opt/game/game (deleted)
+",
+        ));
+        assert_eq!(1, modules.len());
+        assert_eq!("game", modules[0].name);
+        assert!(modules[0].matches("/opt/game/game"));
+        assert_eq!(2, modules[0].segments.len());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_module_find() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let main = main_module(&Process::current()).unwrap();
+        let code = test_module_find as *const () as usize;
+        assert!(main.contains(code));
+        assert!(main
+            .segments
+            .iter()
+            .any(|segment| segment.contains(code) && segment.is_executable()));
+        assert_eq!(main, find(&crate::backend::Local, &main.name).unwrap());
+
+        assert!(matches!(
+            find(&crate::backend::Local, "libdoesnotexist.so"),
+            Err(Error::ModuleNotFound(_))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_module_address() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let main = main_module(&Process::current()).unwrap();
+        let code = test_module_address as *const () as usize;
+        let offset = main.offset_of(code).unwrap();
+
+        unsafe {
+            assert_eq!(
+                Address::new(code as *mut u8).read::<u64>(),
+                main.address(offset).read::<u64>()
+            );
+            assert_eq!(
+                Address::new(code as *mut u8).read::<u64>(),
+                main.address_with_backend(Process::current(), offset)
+                    .read::<u64>()
+            );
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::backend::MemoryBackend;
+use crate::error::{Error, Result};
+use crate::pattern::{Pattern, SCAN_CHUNK_SIZE};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Shared flag a running scan checks between chunks, clones cancel each other.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// How a scan is spread over threads. With no thread count set one thread per
+// available core is used.
+#[derive(Clone, Debug, Default)]
+pub struct ParallelScan {
+    threads: Option<usize>,
+    token: CancellationToken,
+}
+
+impl ParallelScan {
+    pub fn new() -> Self {
+        ParallelScan::default()
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    pub fn token(mut self, token: CancellationToken) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.threads.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1)
+        })
+    }
+
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    // Scans each range for every (overlapping) match. The ranges are cut into
+    // the same chunks the sequential scanners read, each read with
+    // pattern.len() - 1 bytes of overlap, so the result of every chunk is what
+    // the sequential scan would see. Results come back per chunk in address
+    // order, paired with the index of the range they belong to.
+    pub(crate) fn run<B: MemoryBackend + Sync>(
+        &self,
+        backend: &B,
+        pattern: &Pattern,
+        ranges: &[Range<usize>],
+    ) -> Result<Vec<(usize, Result<Vec<usize>>)>> {
+        let mut chunks = vec![];
+        for (index, range) in ranges.iter().enumerate() {
+            let mut address = range.start;
+            while address < range.end {
+                let chunk_size = SCAN_CHUNK_SIZE.min(range.end - address);
+                let read_size = (chunk_size + pattern.len() - 1).min(range.end - address);
+                chunks.push((index, address, read_size));
+                address += chunk_size;
+            }
+        }
+
+        let next_chunk = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Result<Vec<usize>>>>> =
+            Mutex::new((0..chunks.len()).map(|_| None).collect());
+
+        thread::scope(|scope| {
+            for _ in 0..self.thread_count().min(chunks.len()) {
+                scope.spawn(|| {
+                    let mut memory = vec![0u8; SCAN_CHUNK_SIZE + pattern.len() - 1];
+
+                    while !self.token.is_cancelled() {
+                        let chunk = next_chunk.fetch_add(1, Ordering::SeqCst);
+                        if chunk >= chunks.len() {
+                            break;
+                        }
+
+                        let (_, address, read_size) = chunks[chunk];
+                        let found =
+                            unsafe { backend.read_bytes(address, &mut memory[..read_size]) }
+                                .map(|read| find_all(pattern, &memory[..read], address));
+
+                        results.lock().unwrap()[chunk] = Some(found);
+                    }
+                });
+            }
+        });
+
+        if self.token.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        Ok(chunks
+            .iter()
+            .zip(results.into_inner().unwrap())
+            .map(|((index, _, _), found)| (*index, found.expect("chunk was not scanned")))
+            .collect())
+    }
+}
+
+// Addresses of every match in `memory`, which was read from `address`.
+fn find_all(pattern: &Pattern, memory: &[u8], address: usize) -> Vec<usize> {
+    let mut found = vec![];
+    let mut offset = 0;
+
+    while let Some(next) = pattern.find(&memory[offset..]) {
+        found.push(address + offset + next);
+        offset += next + 1;
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::backend::Local;
+
+    #[test]
+    fn test_parallel_run() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = vec![0u8; SCAN_CHUNK_SIZE * 5 + 3];
+        let offsets = [
+            0,
+            SCAN_CHUNK_SIZE - 2,
+            SCAN_CHUNK_SIZE * 3,
+            SCAN_CHUNK_SIZE * 4 + 7,
+            SCAN_CHUNK_SIZE * 5,
+        ];
+        for offset in offsets {
+            bytes[offset..offset + 3].copy_from_slice(&[0xca, 0xfe, 0xba]);
+        }
+
+        let start = bytes.as_ptr() as usize;
+        let pattern = Pattern::parse("ca ?? ba").unwrap();
+
+        for threads in [1, 3, 8] {
+            let chunks = ParallelScan::new()
+                .threads(threads)
+                .run(
+                    &Local,
+                    &pattern,
+                    &[
+                        start..start + SCAN_CHUNK_SIZE * 2,
+                        start + SCAN_CHUNK_SIZE * 2..start + bytes.len(),
+                    ],
+                )
+                .unwrap();
+
+            assert_eq!(
+                vec![0, 0, 1, 1, 1, 1],
+                chunks.iter().map(|(range, _)| *range).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                offsets
+                    .iter()
+                    .map(|offset| start + offset)
+                    .collect::<Vec<_>>(),
+                chunks
+                    .into_iter()
+                    .flat_map(|(_, found)| found.unwrap())
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parallel_cancel() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let bytes = vec![0u8; SCAN_CHUNK_SIZE * 4];
+        let start = bytes.as_ptr() as usize;
+        let token = CancellationToken::new();
+        let parallel = ParallelScan::new().threads(2).token(token.clone());
+
+        token.cancel();
+        assert!(parallel.cancellation_token().is_cancelled());
+        assert!(matches!(
+            parallel.run(
+                &Local,
+                &Pattern::parse("ca fe").unwrap(),
+                std::slice::from_ref(&(start..start + bytes.len()))
+            ),
+            Err(Error::Cancelled)
+        ));
+    }
+}
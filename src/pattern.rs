@@ -0,0 +1,613 @@
+use crate::error::{Error, Result};
+use crate::pattern_format::{self, PatternFormat};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+// bytes fetched from the backend per read while scanning
+pub(crate) const SCAN_CHUNK_SIZE: usize = 0x10000;
+
+// Bytes found all over code and data, most common first. Any byte not listed
+// is taken to be rare.
+const COMMON_BYTES: [u8; 24] = [
+    0x00, 0xff, 0xcc, 0x48, 0x8b, 0x89, 0x0f, 0x24, 0x90, 0x01, 0x4c, 0xe8, 0x08, 0x10, 0x20, 0x83,
+    0x44, 0x85, 0xc0, 0x74, 0x75, 0x8d, 0x41, 0x49,
+];
+
+fn commonness(byte: u8) -> usize {
+    COMMON_BYTES
+        .iter()
+        .position(|common| *common == byte)
+        .map_or(0, |position| COMMON_BYTES.len() - position)
+}
+
+// A compiled byte pattern, only the bits set in a byte's mask have to match.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pattern {
+    bytes: Vec<u8>,
+    mask: Vec<u8>,
+    // the fixed bytes searched for first, candidates are then verified whole
+    anchor: Range<usize>,
+}
+
+impl Pattern {
+    // Parses the "0a 0b ?? 4? 2e" format, a '?' leaves its half of the byte
+    // unmatched. Positions in errors are byte offsets into `pattern` as given.
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut digits: Vec<(usize, char)> = pattern
+            .char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .collect();
+
+        // a lone trailing '?' would only make a wildcard byte, which is dropped
+        if digits.len() % 2 == 1 && digits[digits.len() - 1].1 == '?' {
+            digits.pop();
+        }
+
+        if digits.len() % 2 == 1 {
+            return Err(Error::InvalidPattern {
+                position: digits[digits.len() - 1].0,
+                reason: "incomplete byte",
+            });
+        }
+
+        let mut bytes = Vec::with_capacity(digits.len() / 2);
+        let mut mask = Vec::with_capacity(digits.len() / 2);
+
+        for pair in digits.chunks(2) {
+            let mut byte = 0;
+            let mut byte_mask = 0;
+
+            for (position, c) in pair {
+                byte <<= 4;
+                byte_mask <<= 4;
+
+                if *c != '?' {
+                    byte |= c.to_digit(16).ok_or(Error::InvalidPattern {
+                        position: *position,
+                        reason: "invalid hex digit",
+                    })? as u8;
+                    byte_mask |= 0x0f;
+                }
+            }
+
+            bytes.push(byte);
+            mask.push(byte_mask);
+        }
+
+        Pattern::new(bytes, mask)
+    }
+
+    // `mask` holds the bits of each byte that have to match, 0xff for a fixed
+    // byte and 0x00 for a wildcard.
+    pub fn new(mut bytes: Vec<u8>, mut mask: Vec<u8>) -> Result<Self> {
+        if bytes.len() != mask.len() {
+            return Err(Error::InvalidPattern {
+                position: bytes.len().min(mask.len()),
+                reason: "mask length does not match the bytes",
+            });
+        }
+
+        // trailing wildcards match anything, they only make the pattern longer
+        while mask.last() == Some(&0) {
+            mask.pop();
+            bytes.pop();
+        }
+
+        if bytes.is_empty() {
+            return Err(Error::InvalidPattern {
+                position: 0,
+                reason: "pattern is empty",
+            });
+        }
+
+        Ok(Pattern::with_mask(bytes, mask))
+    }
+
+    fn with_mask(mut bytes: Vec<u8>, mask: Vec<u8>) -> Self {
+        for (byte, mask) in bytes.iter_mut().zip(&mask) {
+            *byte &= mask;
+        }
+
+        let mut pattern = Pattern {
+            bytes,
+            mask,
+            anchor: 0..0,
+        };
+        pattern.anchor = pattern.pick_anchor();
+        pattern
+    }
+
+    fn is_fixed(&self, index: usize) -> bool {
+        self.mask[index] == 0xff
+    }
+
+    // The longest run of fixed bytes, or the rarest fixed byte when there is no
+    // run longer than one. Empty when nothing in the pattern is fixed.
+    fn pick_anchor(&self) -> Range<usize> {
+        let mut longest = 0..0;
+        let mut start = 0;
+
+        for index in 0..=self.len() {
+            if index < self.len() && self.is_fixed(index) {
+                continue;
+            }
+
+            if index - start > longest.len() {
+                longest = start..index;
+            }
+            start = index + 1;
+        }
+
+        if longest.len() > 1 {
+            return longest;
+        }
+
+        (0..self.len())
+            .filter(|index| self.is_fixed(*index))
+            .min_by_key(|index| commonness(self.bytes[*index]))
+            .map_or(0..0, |index| index..index + 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn mask(&self) -> &[u8] {
+        &self.mask
+    }
+
+    // Where in the pattern the fixed bytes searched for first lie, empty when
+    // no byte is fully fixed.
+    pub fn anchor(&self) -> Range<usize> {
+        self.anchor.clone()
+    }
+
+    // `memory` must hold at least len() bytes.
+    pub fn matches(&self, memory: &[u8]) -> bool {
+        let mut j = 0;
+
+        while j < self.bytes.len() && memory[j] & self.mask[j] == self.bytes[j] {
+            j += 1;
+        }
+
+        j == self.bytes.len()
+    }
+
+    // Offset of the first match lying entirely within `haystack`.
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        if haystack.len() < self.len() {
+            return None;
+        }
+
+        let last = haystack.len() - self.len();
+        if self.anchor.is_empty() {
+            return (0..=last).find(|&offset| self.matches(&haystack[offset..]));
+        }
+
+        // the anchor of a match starting at `offset` sits at offset + anchor.start
+        let anchor = &self.bytes[self.anchor.clone()];
+        let end = last + self.anchor.end;
+        let mut from = self.anchor.start;
+
+        while from + anchor.len() <= end {
+            let found = if anchor.len() == 1 {
+                memchr::memchr(anchor[0], &haystack[from..end])
+            } else {
+                memchr::memmem::find(&haystack[from..end], anchor)
+            }?;
+
+            let offset = from + found - self.anchor.start;
+            if self.matches(&haystack[offset..]) {
+                return Some(offset);
+            }
+
+            from += found + 1;
+        }
+
+        None
+    }
+}
+
+// A pattern checked and compiled at build time by pattern!, see there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StaticPattern<const N: usize> {
+    pub bytes: [u8; N],
+    pub mask: [u8; N],
+}
+
+impl<const N: usize> StaticPattern<N> {
+    // Panics on malformed input or when `N` is not static_len(pattern), which
+    // makes it a compile error when evaluated in a constant.
+    pub const fn parse(pattern: &str) -> Self {
+        let pattern = pattern.as_bytes();
+        let ida = is_static_ida(pattern);
+        let mut compiled = StaticPattern {
+            bytes: [0; N],
+            mask: [0; N],
+        };
+        let mut index = 0;
+        let mut len = 0;
+        let mut used = 0;
+
+        while let Some((next, byte, mask)) = next_static_byte(pattern, index, ida) {
+            if len < N {
+                compiled.bytes[len] = byte;
+                compiled.mask[len] = mask;
+            }
+
+            index = next;
+            len += 1;
+            if mask != 0 {
+                used = len;
+            }
+        }
+
+        if used != N {
+            panic!("pattern length does not match its static length");
+        }
+
+        compiled
+    }
+
+    pub fn to_pattern(&self) -> Pattern {
+        Pattern::with_mask(self.bytes.to_vec(), self.mask.to_vec())
+    }
+}
+
+impl<const N: usize> From<StaticPattern<N>> for Pattern {
+    fn from(pattern: StaticPattern<N>) -> Self {
+        pattern.to_pattern()
+    }
+}
+
+// Number of bytes in a Hex, x64dbg or IDA style pattern, trailing wildcard
+// bytes not counted. Panics on malformed input.
+pub const fn static_len(pattern: &str) -> usize {
+    let pattern = pattern.as_bytes();
+    let ida = is_static_ida(pattern);
+    let mut index = 0;
+    let mut count = 0;
+    let mut len = 0;
+
+    while let Some((next, _, mask)) = next_static_byte(pattern, index, ida) {
+        index = next;
+        count += 1;
+        if mask != 0 {
+            len = count;
+        }
+    }
+
+    if len == 0 {
+        panic!("pattern is empty");
+    }
+
+    len
+}
+
+const fn is_whitespace(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+// Mirrors PatternFormat::detect, a token of a single '?' means IDA style.
+const fn is_static_ida(pattern: &[u8]) -> bool {
+    let mut index = 0;
+
+    while index < pattern.len() {
+        if pattern[index] == b'?'
+            && (index == 0 || is_whitespace(pattern[index - 1]))
+            && (index + 1 == pattern.len() || is_whitespace(pattern[index + 1]))
+        {
+            return true;
+        }
+        index += 1;
+    }
+
+    false
+}
+
+// The nibble value and mask of a hex digit or '?'.
+const fn static_nibble(c: u8) -> (u8, u8) {
+    match c {
+        b'?' => (0, 0),
+        b'0'..=b'9' => (c - b'0', 0x0f),
+        b'a'..=b'f' => (c - b'a' + 10, 0x0f),
+        b'A'..=b'F' => (c - b'A' + 10, 0x0f),
+        _ => panic!("invalid hex digit in pattern"),
+    }
+}
+
+const fn skip_whitespace(pattern: &[u8], mut index: usize) -> usize {
+    while index < pattern.len() && is_whitespace(pattern[index]) {
+        index += 1;
+    }
+    index
+}
+
+// The byte starting at or after `index`, with its mask and the index after it,
+// parsed the way Pattern::parse and the IDA format parse it.
+const fn next_static_byte(pattern: &[u8], index: usize, ida: bool) -> Option<(usize, u8, u8)> {
+    let index = skip_whitespace(pattern, index);
+    if index >= pattern.len() {
+        return None;
+    }
+
+    if ida {
+        let mut end = index;
+        while end < pattern.len() && !is_whitespace(pattern[end]) {
+            end += 1;
+        }
+
+        return match end - index {
+            1 if pattern[index] == b'?' => Some((end, 0, 0)),
+            2 if pattern[index] == b'?' && pattern[index + 1] == b'?' => Some((end, 0, 0)),
+            2 if pattern[index] != b'?' && pattern[index + 1] != b'?' => {
+                let (high, _) = static_nibble(pattern[index]);
+                let (low, _) = static_nibble(pattern[index + 1]);
+                Some((end, high << 4 | low, 0xff))
+            }
+            _ => panic!("expected a byte or a wildcard in pattern"),
+        };
+    }
+
+    let second = skip_whitespace(pattern, index + 1);
+    if second >= pattern.len() {
+        if pattern[index] == b'?' {
+            return None;
+        }
+        panic!("incomplete byte in pattern");
+    }
+
+    let (high, high_mask) = static_nibble(pattern[index]);
+    let (low, low_mask) = static_nibble(pattern[second]);
+    Some((second + 1, high << 4 | low, high_mask << 4 | low_mask))
+}
+
+/// Checks and compiles a Hex, x64dbg or IDA style pattern at build time, a
+/// malformed pattern fails to compile. Gives a StaticPattern, which turns into a
+/// Pattern with into() or to_pattern().
+///
+/// ```
+/// let pattern: mnemosyrs::pattern::Pattern = mnemosyrs::pattern!("48 8B ?? ?? 89").into();
+/// assert_eq!(5, pattern.len());
+/// ```
+///
+/// An incomplete byte is a compile error:
+///
+/// ```compile_fail
+/// let pattern = mnemosyrs::pattern!("48 8");
+/// ```
+///
+/// So is a half wildcard in IDA style, which has none:
+///
+/// ```compile_fail
+/// let pattern = mnemosyrs::pattern!("48 ? 4?");
+/// ```
+///
+/// And anything that is not hex:
+///
+/// ```compile_fail
+/// let pattern = mnemosyrs::pattern!("48 zz");
+/// ```
+#[macro_export]
+macro_rules! pattern {
+    ($pattern:expr) => {{
+        const PATTERN: $crate::pattern::StaticPattern<{ $crate::pattern::static_len($pattern) }> =
+            $crate::pattern::StaticPattern::parse($pattern);
+        PATTERN
+    }};
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", PatternFormat::Hex.render(self))
+    }
+}
+
+// Accepts any format pattern_format knows about.
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        pattern_format::parse(pattern)
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_pattern_parse() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let pattern = Pattern::parse("0a 0B ?? ?a   2e ??  ").unwrap();
+        assert_eq!(5, pattern.len());
+        assert_eq!(&[0x0a, 0x0b, 0x00, 0x0a, 0x2e], pattern.bytes());
+        assert_eq!(&[0xff, 0xff, 0x00, 0x0f, 0xff], pattern.mask());
+
+        // a lone trailing '?' and trailing wildcard bytes are dropped, a
+        // trailing half wildcard is not
+        assert_eq!(1, Pattern::parse("0a ?? ?").unwrap().len());
+        assert_eq!(2, Pattern::parse("0a 4?").unwrap().len());
+
+        assert!(matches!(
+            Pattern::parse("?? ??"),
+            Err(Error::InvalidPattern { position: 0, .. })
+        ));
+        assert!(matches!(
+            Pattern::parse("0a 0"),
+            Err(Error::InvalidPattern { position: 3, .. })
+        ));
+        assert!(matches!(
+            Pattern::parse("0a x0"),
+            Err(Error::InvalidPattern { position: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_pattern_find() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let pattern = Pattern::parse("7b ?? 57").unwrap();
+
+        assert!(pattern.matches(&[0x7b, 0x00, 0x57]));
+        assert!(!pattern.matches(&[0x7b, 0x00, 0x58]));
+        assert_eq!(Some(1), pattern.find(&[0x90, 0x7b, 0x01, 0x57, 0x7b]));
+        assert_eq!(None, pattern.find(&[0x90, 0x7b, 0x01]));
+        assert_eq!(None, pattern.find(&[0x7b, 0x01]));
+    }
+
+    #[test]
+    fn test_pattern_new() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let pattern =
+            Pattern::new(vec![0x48, 0x8b, 0x45, 0x00], vec![0xff, 0xff, 0x00, 0x00]).unwrap();
+        assert_eq!(&[0x48, 0x8b], pattern.bytes());
+        assert_eq!(Pattern::parse("48 8b").unwrap(), pattern);
+
+        assert!(matches!(
+            Pattern::new(vec![0x48, 0x8b], vec![0xff]),
+            Err(Error::InvalidPattern { position: 1, .. })
+        ));
+        assert!(matches!(
+            Pattern::new(vec![0x48], vec![0x00]),
+            Err(Error::InvalidPattern { position: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_pattern_from_str() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let pattern: Pattern = "48 8B ? ? 41".parse().unwrap();
+        assert_eq!("48 8b ?? ?? 41", pattern.to_string());
+        assert!("48 8B ? zz".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn test_pattern_static() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        const MOV: StaticPattern<5> = crate::pattern!("48 8B ?? ?? 89 ?? ??");
+        assert_eq!([0x48, 0x8b, 0x00, 0x00, 0x89], MOV.bytes);
+        assert_eq!([0xff, 0xff, 0x00, 0x00, 0xff], MOV.mask);
+
+        for (compiled, runtime) in [
+            (
+                crate::pattern!("48 8B ?? ?? 89").to_pattern(),
+                "48 8B ?? ?? 89",
+            ),
+            (crate::pattern!("48 8B ? ? 89").to_pattern(), "48 8B ? ? 89"),
+            (crate::pattern!("4?8b?5 ??").to_pattern(), "4?8b?5 ??"),
+            (crate::pattern!("e8 ? ? ? ? 90 ?").into(), "e8 ? ? ? ? 90 ?"),
+        ] {
+            assert_eq!(runtime.parse::<Pattern>().unwrap(), compiled);
+        }
+
+        assert_eq!(3, static_len("0a ?? 0b ?? ??"));
+    }
+
+    // Outside of a constant the checks are runtime panics.
+    #[test]
+    #[should_panic]
+    fn test_pattern_static_invalid() {
+        StaticPattern::<2>::parse("48 8");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pattern_static_invalid_ida() {
+        StaticPattern::<3>::parse("48 ? 4?");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pattern_static_wrong_length() {
+        StaticPattern::<3>::parse("48 8b");
+    }
+
+    #[test]
+    fn test_pattern_nibble_wildcards() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let rex = Pattern::parse("4? 8b").unwrap();
+        assert_eq!(&[0xf0, 0xff], rex.mask());
+        for byte in 0x40..=0x4f {
+            assert!(rex.matches(&[byte, 0x8b]));
+        }
+        assert!(!rex.matches(&[0x50, 0x8b]));
+        assert!(!rex.matches(&[0x3f, 0x8b]));
+
+        let low = Pattern::parse("?8").unwrap();
+        assert!(low.matches(&[0x08]) && low.matches(&[0xf8]));
+        assert!(!low.matches(&[0x80]));
+
+        let mov = Pattern::parse("48 b? 00").unwrap();
+        assert_eq!(Some(2), mov.find(&[0x90, 0x48, 0x48, 0xb9, 0x00]));
+        assert_eq!(None, mov.find(&[0x48, 0xc9, 0x00]));
+    }
+
+    #[test]
+    fn test_pattern_anchor() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        // longest solid run
+        assert_eq!(
+            5..8,
+            Pattern::parse("48 ?? 8b 05 ?? 89 45 f8 ?? e8")
+                .unwrap()
+                .anchor
+        );
+        // the rarest single byte otherwise
+        assert_eq!(2..3, Pattern::parse("00 ?? 3d ?? ff").unwrap().anchor);
+        assert_eq!(0..1, Pattern::parse("e8 ?? ?? ?? ?? 8b").unwrap().anchor);
+    }
+
+    // Patterns cut out of random haystacks over a small alphabet, so there are
+    // plenty of partial and overlapping matches, must be found exactly where the
+    // byte by byte loop finds them.
+    #[test]
+    fn test_pattern_find_matches_naive() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut rng = StdRng::seed_from_u64(0x6d6e656d);
+
+        for _ in 0..500 {
+            let haystack: Vec<u8> = (0..rng.gen_range(1..400))
+                .map(|_| [0x00, 0x48, 0x41, 0x8b, 0xe8][rng.gen_range(0..5)])
+                .collect();
+
+            let len = rng.gen_range(1..12.min(haystack.len() + 1));
+            let start = rng.gen_range(0..=haystack.len() - len);
+            let mut bytes = haystack[start..start + len].to_vec();
+            let mask: Vec<u8> = (0..len)
+                .map(|_| [0xff, 0xff, 0xff, 0x00, 0xf0, 0x0f][rng.gen_range(0..6)])
+                .collect();
+            if rng.gen_bool(0.2) {
+                bytes[rng.gen_range(0..len)] ^= 0x01;
+            }
+
+            let pattern = Pattern::with_mask(bytes, mask);
+            for from in 0..haystack.len() {
+                let naive = (from..haystack.len())
+                    .filter(|&offset| offset + len <= haystack.len())
+                    .find(|&offset| pattern.matches(&haystack[offset..]))
+                    .map(|offset| offset - from);
+
+                assert_eq!(naive, pattern.find(&haystack[from..]), "{:?}", pattern);
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::error::{Error, Result};
+use crate::pattern::Pattern;
+use std::fmt::Write;
+
+// The signature notations patterns are exchanged in.
+//
+// Hex:    0a 0b ?? 4? 2e          the crate's own, lowercase
+// X64dbg: 0A 0B ?? 4? 2E          same syntax, rendered uppercase
+// Ida:    0A 0B ? ? 2E            one '?' per wildcard byte
+// Code:   "\x0A\x0B\x00" "xx?"   escaped bytes followed by an x/? mask
+//
+// Ida and Code have no half byte wildcards, rendering a pattern that uses them
+// widens those bytes to full wildcards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PatternFormat {
+    Hex,
+    X64dbg,
+    Ida,
+    Code,
+}
+
+impl PatternFormat {
+    // Hex and X64dbg read the same, so X64dbg signatures detect as Hex.
+    pub fn detect(pattern: &str) -> PatternFormat {
+        if pattern.contains("\\x") || pattern.contains("\\X") {
+            PatternFormat::Code
+        } else if pattern.split_whitespace().any(|token| token == "?") {
+            PatternFormat::Ida
+        } else {
+            PatternFormat::Hex
+        }
+    }
+
+    pub fn parse(self, pattern: &str) -> Result<Pattern> {
+        match self {
+            PatternFormat::Hex | PatternFormat::X64dbg => Pattern::parse(pattern),
+            PatternFormat::Ida => parse_ida(pattern),
+            PatternFormat::Code => parse_code(pattern),
+        }
+    }
+
+    pub fn render(self, pattern: &Pattern) -> String {
+        let bytes = pattern.bytes().iter().zip(pattern.mask());
+
+        match self {
+            PatternFormat::Hex | PatternFormat::X64dbg => {
+                // a nibble only partly masked has to be widened as well
+                let digit = |value: u8, mask: u8| {
+                    if mask != 0x0f {
+                        return '?';
+                    }
+
+                    let digit = char::from_digit(value as u32, 16).unwrap();
+                    if self == PatternFormat::X64dbg {
+                        digit.to_ascii_uppercase()
+                    } else {
+                        digit
+                    }
+                };
+
+                bytes
+                    .map(|(byte, mask)| {
+                        format!(
+                            "{}{}",
+                            digit(byte >> 4, mask >> 4),
+                            digit(byte & 0x0f, mask & 0x0f)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            PatternFormat::Ida => bytes
+                .map(|(byte, mask)| match mask {
+                    0xff => format!("{:02X}", byte),
+                    _ => String::from("?"),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            PatternFormat::Code => {
+                let mut escaped = String::new();
+                let mut mask_string = String::new();
+
+                for (byte, mask) in bytes {
+                    if *mask == 0xff {
+                        write!(escaped, "\\x{:02X}", byte).unwrap();
+                        mask_string.push('x');
+                    } else {
+                        escaped.push_str("\\x00");
+                        mask_string.push('?');
+                    }
+                }
+
+                format!("\"{}\" \"{}\"", escaped, mask_string)
+            }
+        }
+    }
+}
+
+// Parses a pattern in whichever format it is written in.
+pub fn parse(pattern: &str) -> Result<Pattern> {
+    PatternFormat::detect(pattern).parse(pattern)
+}
+
+// Whitespace separated tokens, each a two digit hex byte or "?"/"??".
+fn parse_ida(pattern: &str) -> Result<Pattern> {
+    let mut bytes = vec![];
+    let mut mask = vec![];
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        let token_start = pattern.len() - rest.len() + start;
+        let token = &rest[start..];
+        let token = &token[..token.find(char::is_whitespace).unwrap_or(token.len())];
+        rest = &rest[start + token.len()..];
+
+        if token == "?" || token == "??" {
+            bytes.push(0);
+            mask.push(0);
+            continue;
+        }
+
+        if token.len() != 2 {
+            return Err(Error::InvalidPattern {
+                position: token_start,
+                reason: "expected a byte or a wildcard",
+            });
+        }
+
+        bytes.push(
+            u8::from_str_radix(token, 16).map_err(|_| Error::InvalidPattern {
+                position: token_start,
+                reason: "invalid hex digit",
+            })?,
+        );
+        mask.push(0xff);
+    }
+
+    Pattern::new(bytes, mask)
+}
+
+// \xHH escapes, optionally quoted and separated by commas, followed by an
+// optional mask of 'x' for fixed and '?' for wildcard bytes. Without a mask
+// every byte is fixed.
+fn parse_code(pattern: &str) -> Result<Pattern> {
+    let mut bytes = vec![];
+    let mut mask = vec![];
+    let mut mask_start = None;
+    let mut chars = pattern.char_indices();
+
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '"' | ',' => {}
+            c if c.is_whitespace() => {}
+            '\\' => {
+                if mask_start.is_some() {
+                    return Err(Error::InvalidPattern {
+                        position,
+                        reason: "byte after the mask",
+                    });
+                }
+
+                if !matches!(chars.next(), Some((_, 'x' | 'X'))) {
+                    return Err(Error::InvalidPattern {
+                        position,
+                        reason: "expected \\x escape",
+                    });
+                }
+
+                let mut byte = 0;
+                for _ in 0..2 {
+                    let digit = chars.next().and_then(|(_, c)| c.to_digit(16)).ok_or(
+                        Error::InvalidPattern {
+                            position,
+                            reason: "invalid hex digit",
+                        },
+                    )?;
+                    byte = byte << 4 | digit as u8;
+                }
+                bytes.push(byte);
+            }
+            'x' | 'X' | '?' => {
+                mask_start.get_or_insert(position);
+                mask.push(if c == '?' { 0x00 } else { 0xff });
+            }
+            _ => {
+                return Err(Error::InvalidPattern {
+                    position,
+                    reason: "unexpected character",
+                })
+            }
+        }
+    }
+
+    if mask_start.is_none() {
+        mask = vec![0xff; bytes.len()];
+    }
+
+    if mask.len() != bytes.len() {
+        return Err(Error::InvalidPattern {
+            position: mask_start.unwrap_or(0),
+            reason: "mask length does not match the bytes",
+        });
+    }
+
+    Pattern::new(bytes, mask)
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    #[test]
+    fn test_pattern_format_detect() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        assert_eq!(PatternFormat::Ida, PatternFormat::detect("48 8B ? ? 89"));
+        assert_eq!(PatternFormat::Hex, PatternFormat::detect("48 8B ?? ?? 89"));
+        assert_eq!(PatternFormat::Hex, PatternFormat::detect("488B????89"));
+        assert_eq!(
+            PatternFormat::Code,
+            PatternFormat::detect(r#""\x48\x8B\x00" "xx?""#)
+        );
+    }
+
+    #[test]
+    fn test_pattern_format_parse() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let expected = Pattern::parse("48 8b ?? ?? 89").unwrap();
+
+        for signature in [
+            "48 8B ? ? 89",
+            "48 8B ?? ? 89",
+            "48 8B ?? ?? 89",
+            "488B????89",
+            r#""\x48\x8B\x00\x00\x89" "xx??x""#,
+            r#""\x48\x8B\x00\x00\x89", "xx??x""#,
+            r"\x48\x8B\x00\x00\x89 xx??x",
+        ] {
+            assert_eq!(expected, parse(signature).unwrap(), "{}", signature);
+        }
+
+        assert_eq!(
+            Pattern::parse("48 8b").unwrap(),
+            parse(r"\x48\x8b").unwrap()
+        );
+        assert_eq!(
+            Pattern::parse("4? 8b").unwrap(),
+            PatternFormat::X64dbg.parse("4? 8B").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pattern_format_parse_errors() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        assert!(matches!(
+            PatternFormat::Ida.parse("48 8B ? 4? 89"),
+            Err(Error::InvalidPattern { position: 8, .. })
+        ));
+        assert!(matches!(
+            PatternFormat::Ida.parse("48 8B ? zz"),
+            Err(Error::InvalidPattern { position: 8, .. })
+        ));
+        assert!(matches!(
+            PatternFormat::Ida.parse("? ?"),
+            Err(Error::InvalidPattern { position: 0, .. })
+        ));
+        assert!(matches!(
+            parse(r#""\x48\x8B" "xx?""#),
+            Err(Error::InvalidPattern { position: 12, .. })
+        ));
+        assert!(matches!(
+            parse(r"\x48\x8G"),
+            Err(Error::InvalidPattern { position: 4, .. })
+        ));
+        assert!(matches!(
+            parse(r"\x48 xx \x8B"),
+            Err(Error::InvalidPattern { position: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn test_pattern_format_render() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let pattern = Pattern::parse("48 8b ?? 4? 89").unwrap();
+
+        assert_eq!("48 8b ?? 4? 89", PatternFormat::Hex.render(&pattern));
+        assert_eq!("48 8B ?? 4? 89", PatternFormat::X64dbg.render(&pattern));
+        assert_eq!("48 8B ? ? 89", PatternFormat::Ida.render(&pattern));
+        assert_eq!(
+            r#""\x48\x8B\x00\x00\x89" "xx??x""#,
+            PatternFormat::Code.render(&pattern)
+        );
+
+        let solid = Pattern::parse("48 8b 05 ?? 89").unwrap();
+        for format in [
+            PatternFormat::Hex,
+            PatternFormat::X64dbg,
+            PatternFormat::Ida,
+            PatternFormat::Code,
+        ] {
+            assert_eq!(solid, format.parse(&format.render(&solid)).unwrap());
+            assert_eq!(solid, parse(&format.render(&solid)).unwrap());
+        }
+    }
+}
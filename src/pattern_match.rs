@@ -1,4 +1,14 @@
-pub struct PatternMatch {
+use crate::address::Address;
+use crate::backend::{Local, MemoryBackend};
+use crate::error::{Error, Result};
+use crate::module::Module;
+use crate::parallel::ParallelScan;
+use crate::pattern::{Pattern, SCAN_CHUNK_SIZE};
+use crate::pattern_format::{self, PatternFormat};
+use crate::post_op::PostOp;
+use std::ops::Range;
+
+pub struct PatternMatch<B: MemoryBackend = Local> {
     pattern: String,
     pattern_size: usize,
 
@@ -6,97 +16,409 @@ pub struct PatternMatch {
     memory_size: usize,
     current_address: *mut u8,
 
-    byte_array: Vec<u8>,
-    mask: Vec<u8>,
+    // the parts of the memory range that are read, relative to memory_start.
+    // Anything between them is skipped, e.g. the gaps between module segments.
+    ranges: Vec<Range<usize>>,
+
+    compiled: Pattern,
+
+    backend: B,
 }
 
 impl PatternMatch {
     pub fn new(pattern: String, memory_start: *const u8, memory_size: usize) -> Self {
-        let mut pattern_match = PatternMatch {
-            pattern: pattern.clone(),
-            pattern_size: 0,
+        PatternMatch::with_backend(Local, pattern, memory_start, memory_size)
+    }
 
-            memory_start: memory_start,
-            memory_size: memory_size,
-            current_address: memory_start as *mut u8,
+    pub fn try_new(pattern: String, memory_start: *const u8, memory_size: usize) -> Result<Self> {
+        PatternMatch::try_with_backend(Local, pattern, memory_start, memory_size)
+    }
+
+    pub fn in_module(pattern: String, module: &Module) -> Self {
+        PatternMatch::with_backend_in_module(Local, pattern, module)
+    }
+
+    pub fn try_in_module(pattern: String, module: &Module) -> Result<Self> {
+        PatternMatch::try_with_backend_in_module(Local, pattern, module)
+    }
+
+    pub fn from_pattern(
+        pattern: impl Into<Pattern>,
+        memory_start: *const u8,
+        memory_size: usize,
+    ) -> Self {
+        PatternMatch::with_backend_from_pattern(Local, pattern, memory_start, memory_size)
+    }
+}
+
+impl<B: MemoryBackend> PatternMatch<B> {
+    pub fn with_backend(
+        backend: B,
+        pattern: String,
+        memory_start: *const u8,
+        memory_size: usize,
+    ) -> Self {
+        PatternMatch::try_with_backend(backend, pattern, memory_start, memory_size)
+            .unwrap_or_else(|error| panic!("pattern is in unexpected format: {}", error))
+    }
+
+    pub fn try_with_backend(
+        backend: B,
+        pattern: String,
+        memory_start: *const u8,
+        memory_size: usize,
+    ) -> Result<Self> {
+        let mut pattern_match = PatternMatch::with_backend_from_pattern(
+            backend,
+            pattern_format::parse(&pattern)?,
+            memory_start,
+            memory_size,
+        );
 
-            byte_array: vec![],
-            mask: vec![],
+        // the caller's pattern as written. Hex patterns lose their whitespace and
+        // trailing wildcards as they always did, the other formats need their
+        // spacing to read back the same and are kept verbatim.
+        pattern_match.pattern = match PatternFormat::detect(&pattern) {
+            PatternFormat::Hex => pattern
+                .trim_end_matches(|c: char| c == '?' || c.is_whitespace())
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect(),
+            _ => pattern,
         };
 
-        pattern_match.pattern = pattern_match
-            .pattern
-            .trim_end_matches(|c: char| c == '?' || c.is_whitespace())
-            .chars()
-            .filter(|c| !c.is_whitespace())
-            .collect();
+        Ok(pattern_match)
+    }
 
-        if pattern_match.pattern.is_empty() || pattern_match.pattern.len() % 2 == 1 {
-            panic!("pattern is in unexpected format");
-        }
+    // For patterns that are already compiled, e.g. by pattern!.
+    pub fn with_backend_from_pattern(
+        backend: B,
+        pattern: impl Into<Pattern>,
+        memory_start: *const u8,
+        memory_size: usize,
+    ) -> Self {
+        let compiled = pattern.into();
 
-        pattern_match.pattern_size = pattern_match.pattern.len() / 2;
-
-        pattern_match.byte_array.reserve(pattern_match.pattern_size);
-        pattern_match.mask.reserve(pattern_match.pattern_size);
-
-        for n in (0..pattern_match.pattern.len()).step_by(2) {
-            if pattern_match.pattern.chars().nth(n).unwrap() == '?'
-                || pattern_match.pattern.chars().nth(n + 1).unwrap() == '?'
-            {
-                pattern_match.mask.push(1);
-                pattern_match.byte_array.push(0);
-            } else {
-                pattern_match.mask.push(0);
-                pattern_match.byte_array.push(
-                    u8::from_str_radix(&pattern_match.pattern[n..n + 2], 16)
-                        .ok()
-                        .unwrap(),
-                );
-            }
+        PatternMatch {
+            pattern: compiled.to_string().replace(' ', ""),
+            pattern_size: compiled.len(),
+
+            memory_start,
+            memory_size,
+            current_address: memory_start as *mut u8,
+
+            ranges: std::iter::once(0..memory_size).collect(),
+
+            compiled,
+
+            backend,
         }
+    }
+
+    pub fn with_backend_in_module(backend: B, pattern: String, module: &Module) -> Self {
+        PatternMatch::try_with_backend_in_module(backend, pattern, module)
+            .unwrap_or_else(|error| panic!("pattern is in unexpected format: {}", error))
+    }
+
+    // Only the readable segments of the module are searched.
+    pub fn try_with_backend_in_module(
+        backend: B,
+        pattern: String,
+        module: &Module,
+    ) -> Result<Self> {
+        let mut pattern_match = PatternMatch::try_with_backend(
+            backend,
+            pattern,
+            module.base as *const u8,
+            module.size,
+        )?;
+        pattern_match.ranges = module
+            .readable_ranges()
+            .into_iter()
+            .map(|range| range.start - module.base..range.end - module.base)
+            .collect();
+
+        Ok(pattern_match)
+    }
 
-        pattern_match
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn compiled(&self) -> &Pattern {
+        &self.compiled
     }
 
     pub fn find_address(&mut self) -> *const u8 {
+        self.try_find_address().unwrap_or(std::ptr::null())
+    }
+
+    pub fn try_find_address(&mut self) -> Result<*const u8> {
         unsafe { self.find_address_from(self.memory_start as *mut u8) }
     }
 
     pub fn find_next_address(&mut self) -> *const u8 {
+        self.try_find_next_address().unwrap_or(std::ptr::null())
+    }
+
+    pub fn try_find_next_address(&mut self) -> Result<*const u8> {
         unsafe { self.find_address_from(self.current_address.add(1)) }
     }
 
-    unsafe fn find_address_from(&mut self, address_from: *mut u8) -> *const u8 {
-        for offset in 0..self.memory_size {
-            self.current_address = address_from.add(offset);
+    // Matches starting at or after `offset` bytes into the memory range.
+    pub fn matches_from(&self, offset: usize) -> Matches<'_, B> {
+        let offset = offset.min(self.memory_size);
 
-            if self.try_match_at_current_address() {
-                return self.current_address;
+        Matches {
+            pattern_match: self,
+            overlapping: true,
+
+            next_chunk: offset,
+            next_offset: offset,
+
+            memory: vec![],
+            chunk_offset: offset,
+            searched: 0,
+
+            error: None,
+        }
+    }
+
+    pub fn matches(&self) -> Matches<'_, B> {
+        self.matches_from(0)
+    }
+
+    pub fn count(&self) -> usize {
+        self.matches().offsets().count()
+    }
+
+    pub fn try_count(&self) -> Result<usize> {
+        let mut offsets = self.matches().offsets();
+        let count = offsets.by_ref().count();
+
+        match offsets.matches.error.take() {
+            Some(error) => Err(error),
+            None => Ok(count),
+        }
+    }
+
+    unsafe fn find_address_from(&mut self, address_from: *mut u8) -> Result<*const u8> {
+        let mut matches = self.matches_from(address_from as usize - self.memory_start as usize);
+        let found = matches.next_offset();
+
+        if let Some(error) = matches.error.take() {
+            return Err(error);
+        }
+
+        match found {
+            Some(offset) => {
+                self.current_address = (self.memory_start as *mut u8).add(offset);
+                Ok(self.current_address)
+            }
+            None => {
+                self.current_address = (self.memory_start as *mut u8).add(self.memory_size);
+                Ok(std::ptr::null())
             }
         }
+    }
+}
+
+impl<B: MemoryBackend + Clone> PatternMatch<B> {
+    pub fn find_all(&self) -> Vec<Address<B>> {
+        self.matches().collect()
+    }
+
+    pub fn try_find_all(&self) -> Result<Vec<Address<B>>> {
+        let mut matches = self.matches();
+        let found = matches.by_ref().collect();
+
+        match matches.error.take() {
+            Some(error) => Err(error),
+            None => Ok(found),
+        }
+    }
+}
+
+impl<B: MemoryBackend + Clone> PatternMatch<B> {
+    // The first match taken through `ops`, see PostOp.
+    pub fn try_find_resolved(&mut self, ops: &[PostOp]) -> Result<Address<B>> {
+        let found = self.try_find_address()?;
+        if found.is_null() {
+            return Err(Error::PatternNotFound(self.pattern.clone()));
+        }
 
-        std::ptr::null()
+        unsafe { Address::with_backend(self.backend.clone(), found as *mut u8).try_resolve(ops) }
     }
 
-    unsafe fn try_match_at_current_address(&mut self) -> bool {
-        let mut j = 0;
+    pub fn find_resolved(&mut self, ops: &[PostOp]) -> Option<Address<B>> {
+        self.try_find_resolved(ops).ok()
+    }
+}
+
+impl<B: MemoryBackend + Clone + Sync> PatternMatch<B> {
+    // Any error, cancellation included, leaves no matches at all.
+    pub fn find_all_parallel(&self, parallel: &ParallelScan) -> Vec<Address<B>> {
+        self.try_find_all_parallel(parallel).unwrap_or_default()
+    }
 
-        while j < self.pattern_size
-            && (self.mask[j] == 0x01 || &*self.current_address.add(j) ^ self.byte_array[j] == 0)
-        {
-            j += 1;
+    // Same matches in the same order as try_find_all, found on several threads.
+    pub fn try_find_all_parallel(&self, parallel: &ParallelScan) -> Result<Vec<Address<B>>> {
+        let start = self.memory_start as usize;
+        let ranges: Vec<_> = self
+            .ranges
+            .iter()
+            .map(|range| start + range.start..start + range.end)
+            .collect();
+        let mut found = vec![];
+
+        for (_, chunk) in parallel.run(&self.backend, &self.compiled, &ranges)? {
+            found.extend(
+                chunk?
+                    .into_iter()
+                    .map(|address| Address::with_backend(self.backend.clone(), address as *mut u8)),
+            );
         }
 
-        j == self.pattern_size
+        Ok(found)
+    }
+}
+
+// Walks the matches of a PatternMatch without touching its current address. A
+// failed read ends the iteration, the error is kept in error().
+pub struct Matches<'a, B: MemoryBackend> {
+    pattern_match: &'a PatternMatch<B>,
+    overlapping: bool,
+
+    // offsets are relative to the start of the memory range
+    next_chunk: usize,
+    next_offset: usize,
+
+    memory: Vec<u8>,
+    chunk_offset: usize,
+    searched: usize,
+
+    error: Option<Error>,
+}
+
+impl<'a, B: MemoryBackend> Matches<'a, B> {
+    // Overlapping matching (the default) resumes one byte after each match,
+    // non-overlapping matching resumes after its last byte.
+    pub fn overlapping(mut self, overlapping: bool) -> Self {
+        self.overlapping = overlapping;
+        self
+    }
+
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    pub fn offsets(self) -> Offsets<'a, B> {
+        Offsets { matches: self }
+    }
+
+    pub fn next_offset(&mut self) -> Option<usize> {
+        let pattern_match = self.pattern_match;
+        let pattern_size = pattern_match.pattern_size;
+
+        loop {
+            let from = self.next_offset.max(self.chunk_offset) - self.chunk_offset;
+            if from < self.searched {
+                if let Some(offset) = pattern_match
+                    .compiled
+                    .find(&self.memory[from..self.searched])
+                {
+                    let offset = self.chunk_offset + from + offset;
+                    self.next_offset = offset + if self.overlapping { 1 } else { pattern_size };
+                    return Some(offset);
+                }
+            }
+
+            if self.error.is_some() {
+                return None;
+            }
+
+            let range = pattern_match
+                .ranges
+                .iter()
+                .find(|range| self.next_chunk < range.end)?;
+            self.next_chunk = self.next_chunk.max(range.start);
+
+            // each chunk is read with pattern_size - 1 extra bytes so matches
+            // straddling two chunks are still seen whole, but never past the end
+            // of the range being read.
+            let remaining = range.end - self.next_chunk;
+            let chunk_size = SCAN_CHUNK_SIZE.min(remaining);
+            let read_size = (chunk_size + pattern_size - 1).min(remaining);
+
+            self.memory.resize(SCAN_CHUNK_SIZE + pattern_size - 1, 0);
+            match unsafe {
+                pattern_match.backend.read_bytes(
+                    pattern_match.memory_start as usize + self.next_chunk,
+                    &mut self.memory[..read_size],
+                )
+            } {
+                Ok(read) => {
+                    self.chunk_offset = self.next_chunk;
+                    self.searched = read.min(read_size);
+                    self.next_chunk += chunk_size;
+
+                    // the bytes that did come are still searched, the rest of
+                    // the range is not
+                    if read < read_size {
+                        self.error = Some(Error::PartialRead {
+                            address: pattern_match.memory_start as usize + self.chunk_offset,
+                            expected: read_size,
+                            read,
+                        });
+                    }
+                }
+                Err(error) => {
+                    self.searched = 0;
+                    self.error = Some(error);
+                }
+            }
+        }
+    }
+}
+
+impl<'a, B: MemoryBackend + Clone> Iterator for Matches<'a, B> {
+    type Item = Address<B>;
+
+    fn next(&mut self) -> Option<Address<B>> {
+        let offset = self.next_offset()?;
+
+        Some(Address::with_backend(
+            self.pattern_match.backend.clone(),
+            (self.pattern_match.memory_start as usize + offset) as *mut u8,
+        ))
+    }
+}
+
+pub struct Offsets<'a, B: MemoryBackend> {
+    matches: Matches<'a, B>,
+}
+
+impl<'a, B: MemoryBackend> Offsets<'a, B> {
+    pub fn error(&self) -> Option<&Error> {
+        self.matches.error()
+    }
+}
+
+impl<'a, B: MemoryBackend> Iterator for Offsets<'a, B> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        self.matches.next_offset()
     }
 }
 
 #[cfg(test)]
 mod unit_test {
     use super::*;
+    use crate::backend::Buffer;
 
     #[test]
+    #[allow(clippy::unnecessary_cast)]
     fn test_pattern_match_new() {
         std::env::set_var("RUST_BACKTRACE", "1");
 
@@ -110,6 +432,40 @@ mod unit_test {
         assert_eq!(pattern_match.pattern_size, sanitized_pattern.len() / 2);
     }
 
+    #[test]
+    fn test_pattern_match_new_formats() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let compiled = Pattern::parse("0a 0b ?? ?? ?? 2e").unwrap();
+
+        let pattern_match =
+            PatternMatch::new(String::from("0A 0B ? ? ? 2E ?"), std::ptr::null(), 0);
+        assert_eq!("0A 0B ? ? ? 2E ?", pattern_match.pattern());
+        assert_eq!(&compiled, pattern_match.compiled());
+        assert_eq!(
+            compiled,
+            pattern_format::parse(pattern_match.pattern()).unwrap()
+        );
+
+        let pattern_match = PatternMatch::new(
+            String::from(r#""\x0A\x0B\x00\x00\x00\x2E" "xx???x""#),
+            std::ptr::null(),
+            0,
+        );
+        assert_eq!(
+            r#""\x0A\x0B\x00\x00\x00\x2E" "xx???x""#,
+            pattern_match.pattern()
+        );
+        assert_eq!(&compiled, pattern_match.compiled());
+
+        let pattern_match =
+            PatternMatch::new(String::from("0A 0B ?? ?? ?? 2E"), std::ptr::null(), 0);
+        assert_eq!("0A0B??????2E", pattern_match.pattern());
+
+        let pattern_match = PatternMatch::from_pattern(compiled.clone(), std::ptr::null(), 0);
+        assert_eq!("0a0b??????2e", pattern_match.pattern());
+    }
+
     #[test]
     fn test_pattern_match_find_address() {
         std::env::set_var("RUST_BACKTRACE", "1");
@@ -171,4 +527,426 @@ mod unit_test {
             );
         }
     }
+
+    #[test]
+    fn test_pattern_match_buffer_backend() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(
+            0x400000,
+            vec![
+                0xf1, 0x80, 0xd7, 0x50, 0x1a, 0x7b, 0x69, 0x57, 0x07, 0x80, 0xbc, 0x27, 0xc7, 0x5e,
+                0x88, 0x0c, 0x7b, 0xa0, 0x57, 0x07, 0x2b, 0xbc, 0xdd, 0xc7,
+            ],
+        );
+
+        let mut pattern_match = PatternMatch::with_backend(
+            &buffer,
+            String::from("7b ?? 57 07 ?? bc ?? c7"),
+            buffer.base() as *const u8,
+            buffer.len(),
+        );
+
+        assert_eq!(0x400005 as *const u8, pattern_match.find_address());
+        assert_eq!(0x400010 as *const u8, pattern_match.find_next_address());
+        assert_eq!(std::ptr::null(), pattern_match.find_next_address());
+    }
+
+    #[test]
+    fn test_pattern_match_stays_in_bounds() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(
+            0x400000,
+            vec![
+                0x90, 0x7b, 0x57, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x7b, 0x57,
+            ],
+        );
+
+        // the second match lies outside of the 8 bytes being searched
+        let mut pattern_match = PatternMatch::with_backend(
+            &buffer,
+            String::from("7b 57"),
+            buffer.base() as *const u8,
+            8,
+        );
+
+        assert_eq!(0x400001 as *const u8, pattern_match.find_address());
+        assert_eq!(std::ptr::null(), pattern_match.find_next_address());
+        assert_eq!(std::ptr::null(), pattern_match.find_next_address());
+        assert_eq!(1, pattern_match.count());
+
+        // nor may a match run past the end
+        let pattern_match = PatternMatch::with_backend(
+            &buffer,
+            String::from("7b 57"),
+            buffer.base() as *const u8,
+            11,
+        );
+        assert_eq!(1, pattern_match.count());
+    }
+
+    #[test]
+    fn test_pattern_match_matches() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(
+            0x400000,
+            vec![0xaa, 0xaa, 0xaa, 0xaa, 0x90, 0xaa, 0xaa, 0x90],
+        );
+        let pattern_match = PatternMatch::with_backend(
+            &buffer,
+            String::from("aa aa"),
+            buffer.base() as *const u8,
+            buffer.len(),
+        );
+
+        assert_eq!(
+            vec![0, 1, 2, 5],
+            pattern_match.matches().offsets().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![0, 2, 5],
+            pattern_match
+                .matches()
+                .overlapping(false)
+                .offsets()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![0x400002 as *mut u8, 0x400005 as *mut u8],
+            pattern_match
+                .matches_from(2)
+                .overlapping(false)
+                .map(|address| address.ptr())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(0, pattern_match.matches_from(100).count());
+
+        let found = pattern_match.find_all();
+        assert_eq!(4, found.len());
+        assert_eq!(0x400005 as *mut u8, found[3].ptr());
+        assert_eq!(4, pattern_match.count());
+    }
+
+    #[test]
+    fn test_pattern_match_matches_large() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = vec![0u8; SCAN_CHUNK_SIZE * 2 + 5];
+        for offset in [0, SCAN_CHUNK_SIZE - 1, SCAN_CHUNK_SIZE * 2 + 2] {
+            bytes[offset..offset + 3].copy_from_slice(&[0x12, 0x34, 0x56]);
+        }
+
+        let pattern_match =
+            PatternMatch::new(String::from("12 34 56"), bytes.as_ptr(), bytes.len());
+        assert_eq!(
+            vec![0, SCAN_CHUNK_SIZE - 1, SCAN_CHUNK_SIZE * 2 + 2],
+            pattern_match.matches().offsets().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_pattern_match_find_all_parallel() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = vec![0u8; SCAN_CHUNK_SIZE * 3 + 1];
+        for offset in (0..bytes.len() - 2).step_by(0x1ff7) {
+            bytes[offset..offset + 2].copy_from_slice(&[0x0f, 0x1f]);
+        }
+
+        let pattern_match = PatternMatch::new(String::from("0f 1f"), bytes.as_ptr(), bytes.len());
+        let expected: Vec<_> = pattern_match
+            .find_all()
+            .iter()
+            .map(|address| address.ptr())
+            .collect();
+        assert!(expected.len() > 20);
+
+        for threads in [1, 4] {
+            assert_eq!(
+                expected,
+                pattern_match
+                    .find_all_parallel(&ParallelScan::new().threads(threads))
+                    .iter()
+                    .map(|address| address.ptr())
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        let token = crate::parallel::CancellationToken::new();
+        token.cancel();
+        assert!(matches!(
+            pattern_match.try_find_all_parallel(&ParallelScan::new().token(token)),
+            Err(Error::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_pattern_match_matches_error() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x400000, vec![0x7b, 0x57]);
+        let pattern_match =
+            PatternMatch::with_backend(&buffer, String::from("7b 57"), 0x500000 as *const u8, 2);
+
+        let mut matches = pattern_match.matches();
+        assert!(matches.next().is_none());
+        assert!(matches!(matches.error(), Some(Error::Unmapped(0x500000))));
+        assert!(matches!(
+            pattern_match.try_count(),
+            Err(Error::Unmapped(0x500000))
+        ));
+        assert!(pattern_match.try_find_all().is_err());
+
+        // a short read still yields what was read, then ends with the error
+        let buffer = Buffer::new(0x400000, vec![0, 0, 0, 0, 0x7b, 0x57, 0, 0]);
+        let pattern_match =
+            PatternMatch::with_backend(&buffer, String::from("7b 57"), 0x400000 as *const u8, 0x20);
+
+        let mut matches = pattern_match.matches();
+        assert_eq!(0x400004 as *mut u8, matches.next().unwrap().ptr());
+        assert!(matches.next().is_none());
+        assert!(matches!(
+            matches.error(),
+            Some(Error::PartialRead {
+                address: 0x400000,
+                expected: 0x20,
+                read: 8
+            })
+        ));
+        assert!(pattern_match.try_count().is_err());
+    }
+
+    #[test]
+    fn test_pattern_match_nibble_wildcards() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(
+            0x400000,
+            vec![
+                0x48, 0x8b, 0x05, 0x4c, 0x8b, 0x0d, 0x3f, 0x8b, 0x0d, 0x41, 0x8b, 0x15,
+            ],
+        );
+        let pattern_match = PatternMatch::with_backend(
+            &buffer,
+            String::from("4? 8b ?5"),
+            buffer.base() as *const u8,
+            buffer.len(),
+        );
+
+        assert_eq!("4?8b?5", pattern_match.pattern());
+        assert_eq!(
+            vec![0, 9],
+            pattern_match.matches().offsets().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_pattern_match_from_pattern() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let haystack = [0x90, 0x48, 0x8b, 0x05, 0x10, 0x20, 0x89, 0x90];
+        let mut pattern_match = PatternMatch::from_pattern(
+            crate::pattern!("48 8B ?? ?? ?? 89"),
+            haystack.as_ptr(),
+            haystack.len(),
+        );
+
+        assert_eq!("488b??????89", pattern_match.pattern());
+        assert_eq!(
+            unsafe { haystack.as_ptr().add(1) },
+            pattern_match.find_address()
+        );
+    }
+
+    #[test]
+    fn test_pattern_match_find_resolved() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = vec![
+            0x90, 0x48, 0x8b, 0x05, 0x08, 0x00, 0x00, 0x00, 0xe8, 0xf4, 0xff, 0xff, 0xff, 0x90,
+            0x90, 0x90,
+        ];
+        bytes.extend_from_slice(&0x400001usize.to_ne_bytes());
+        let buffer = Buffer::new(0x400000, bytes);
+
+        let mut pattern_match = PatternMatch::with_backend(
+            &buffer,
+            String::from("48 8B 05 ? ? ? ? E8"),
+            buffer.base() as *const u8,
+            buffer.len(),
+        );
+
+        assert_eq!(
+            0x400010 as *mut u8,
+            pattern_match
+                .find_resolved(&[PostOp::rip(3)])
+                .unwrap()
+                .ptr()
+        );
+        assert_eq!(
+            0x400001 as *mut u8,
+            pattern_match
+                .find_resolved(&[PostOp::rip(3), PostOp::Deref])
+                .unwrap()
+                .ptr()
+        );
+        assert_eq!(
+            0x400001 as *mut u8,
+            pattern_match
+                .find_resolved(&[PostOp::Add(7), PostOp::FollowBranch])
+                .unwrap()
+                .ptr()
+        );
+
+        let mut missing = PatternMatch::with_backend(
+            &buffer,
+            String::from("0f 0b"),
+            buffer.base() as *const u8,
+            buffer.len(),
+        );
+        assert!(matches!(
+            missing.try_find_resolved(&[]),
+            Err(Error::PatternNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_pattern_match_try_new() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        assert!(matches!(
+            PatternMatch::try_new(String::from("  ?? "), std::ptr::null(), 0),
+            Err(Error::InvalidPattern { position: 0, .. })
+        ));
+        assert!(matches!(
+            PatternMatch::try_new(String::from("0a 0b 2"), std::ptr::null(), 0),
+            Err(Error::InvalidPattern { position: 6, .. })
+        ));
+        assert!(matches!(
+            PatternMatch::try_new(String::from("0a zb"), std::ptr::null(), 0),
+            Err(Error::InvalidPattern { position: 3, .. })
+        ));
+        assert!(PatternMatch::try_new(String::from("0a ?? 0b"), std::ptr::null(), 0).is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pattern_match_new_invalid() {
+        PatternMatch::new(String::from("0a 0"), std::ptr::null(), 0);
+    }
+
+    #[test]
+    fn test_pattern_match_try_find_address() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x400000, vec![0x90, 0x7b, 0x57, 0x90]);
+
+        assert_eq!(
+            0x400001 as *const u8,
+            PatternMatch::try_with_backend(
+                &buffer,
+                String::from("7b 57"),
+                0x400000 as *const u8,
+                buffer.len()
+            )
+            .unwrap()
+            .try_find_address()
+            .unwrap()
+        );
+        assert!(matches!(
+            PatternMatch::try_with_backend(
+                &buffer,
+                String::from("7b 57"),
+                0x500000 as *const u8,
+                buffer.len()
+            )
+            .unwrap()
+            .try_find_address(),
+            Err(Error::Unmapped(0x500000))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pattern_match_in_module() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let process = crate::process::Process::current();
+        let main = crate::module::main_module(&process).unwrap();
+        let code = test_pattern_match_in_module as *const () as *mut u8;
+        let signature = crate::util::bytes_to_string(
+            &unsafe { crate::address::Address::new(code).read_memory(32) },
+            crate::util::Lettercase::Uppercase,
+            " ",
+        );
+
+        let found = PatternMatch::in_module(signature.clone(), &main).find_address();
+        assert!(!found.is_null() && main.contains(found as usize));
+
+        assert_eq!(
+            found,
+            PatternMatch::with_backend_in_module(process, signature, &main).find_address()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pattern_match_in_module_with_hole() {
+        use std::os::unix::io::AsRawFd;
+
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let page_size = crate::protection::page_size();
+        let marker = vec![0x6d, 0x6e, 0x65, 0x6d, 0x6f, 0xfe, 0xed, 0xfa, 0xce];
+        let mut bytes = vec![0u8; 3 * page_size];
+        bytes[0x10..0x10 + marker.len()].copy_from_slice(&marker);
+        bytes[2 * page_size + 0x20..2 * page_size + 0x20 + marker.len()].copy_from_slice(&marker);
+
+        let path = std::env::temp_dir().join(format!("mnemosyrs_hole_{}.so", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+
+        unsafe {
+            // a module whose middle page is unmapped again
+            let base = libc::mmap(
+                std::ptr::null_mut(),
+                bytes.len(),
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            );
+            assert_ne!(libc::MAP_FAILED, base);
+            let base = base as usize;
+            assert_eq!(0, libc::munmap((base + page_size) as *mut _, page_size));
+
+            let module = crate::module::find(&Local, path.to_str().unwrap()).unwrap();
+            assert_eq!(base..base + 3 * page_size, module.range());
+
+            let signature =
+                crate::util::bytes_to_string(&marker, crate::util::Lettercase::Uppercase, " ");
+            let mut pattern_match = PatternMatch::in_module(signature, &module);
+            assert_eq!((base + 0x10) as *const u8, pattern_match.find_address());
+            assert_eq!(
+                (base + 2 * page_size + 0x20) as *const u8,
+                pattern_match.find_next_address()
+            );
+            assert_eq!(std::ptr::null(), pattern_match.find_next_address());
+            assert_eq!(2, pattern_match.try_count().unwrap());
+            assert_eq!(
+                2,
+                pattern_match
+                    .try_find_all_parallel(&ParallelScan::new().threads(2))
+                    .unwrap()
+                    .len()
+            );
+
+            libc::munmap(base as *mut _, page_size);
+            libc::munmap((base + 2 * page_size) as *mut _, page_size);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,384 @@
+use crate::backend::MemoryBackend;
+use crate::error::Result;
+use crate::module::Module;
+use crate::pattern::{Pattern, SCAN_CHUNK_SIZE};
+use crate::pattern_format;
+use aho_corasick::AhoCorasick;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+// Many named patterns found in a single pass over memory. The anchors of all
+// patterns go into one Aho-Corasick automaton, each hit is verified against the
+// whole pattern.
+pub struct PatternSet {
+    names: Vec<String>,
+    patterns: Vec<Pattern>,
+    max_len: usize,
+
+    automaton: AhoCorasick,
+    // indices of the patterns in the automaton, by automaton pattern id
+    anchored: Vec<usize>,
+    // patterns without a fully fixed byte, searched for on their own
+    unanchored: Vec<usize>,
+}
+
+impl PatternSet {
+    // A later pattern replaces an earlier one with the same name.
+    pub fn new<I, N>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = (N, Pattern)>,
+        N: Into<String>,
+    {
+        let mut names: Vec<String> = vec![];
+        let mut compiled: Vec<Pattern> = vec![];
+
+        for (name, pattern) in patterns {
+            let name = name.into();
+            match names.iter().position(|existing| *existing == name) {
+                Some(index) => compiled[index] = pattern,
+                None => {
+                    names.push(name);
+                    compiled.push(pattern);
+                }
+            }
+        }
+
+        let (anchored, unanchored): (Vec<usize>, Vec<usize>) =
+            (0..compiled.len()).partition(|index| !compiled[*index].anchor().is_empty());
+        let automaton = AhoCorasick::new(
+            anchored
+                .iter()
+                .map(|index| &compiled[*index].bytes()[compiled[*index].anchor()]),
+        )
+        .expect("failed to build the pattern automaton");
+
+        PatternSet {
+            max_len: compiled
+                .iter()
+                .map(|pattern| pattern.len())
+                .max()
+                .unwrap_or(1),
+            names,
+            patterns: compiled,
+
+            automaton,
+            anchored,
+            unanchored,
+        }
+    }
+
+    // Patterns in any format pattern_format knows about.
+    pub fn parse<'a, I>(patterns: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let patterns = patterns
+            .into_iter()
+            .map(|(name, pattern)| Ok((name, pattern_format::parse(pattern)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(PatternSet::new(patterns))
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Pattern> {
+        self.names
+            .iter()
+            .position(|existing| existing == name)
+            .map(|index| &self.patterns[index])
+    }
+
+    /// # Safety
+    /// With Local, the `memory_size` bytes from `memory_start` must be readable.
+    pub unsafe fn scan<B: MemoryBackend>(
+        &self,
+        backend: &B,
+        memory_start: usize,
+        memory_size: usize,
+    ) -> Result<PatternSetMatches> {
+        self.scan_ranges(
+            backend,
+            std::slice::from_ref(&(memory_start..memory_start + memory_size)),
+        )
+    }
+
+    /// # Safety
+    /// With Local, the module must still be mapped.
+    pub unsafe fn scan_module<B: MemoryBackend>(
+        &self,
+        backend: &B,
+        module: &Module,
+    ) -> Result<PatternSetMatches> {
+        self.scan_ranges(backend, &module.readable_ranges())
+    }
+
+    // Matches may not run from one range into the next.
+    unsafe fn scan_ranges<B: MemoryBackend>(
+        &self,
+        backend: &B,
+        ranges: &[Range<usize>],
+    ) -> Result<PatternSetMatches> {
+        let mut found = vec![vec![]; self.patterns.len()];
+
+        if !self.is_empty() {
+            // each chunk is read with max_len - 1 extra bytes so matches
+            // straddling two chunks are still seen whole.
+            let mut memory = vec![0u8; SCAN_CHUNK_SIZE + self.max_len - 1];
+
+            for range in ranges {
+                let mut chunk_start = range.start;
+
+                while chunk_start < range.end {
+                    let remaining = range.end - chunk_start;
+                    let chunk_size = SCAN_CHUNK_SIZE.min(remaining);
+                    let read_size = (chunk_size + self.max_len - 1).min(remaining);
+                    let read = backend.read_bytes(chunk_start, &mut memory[..read_size])?;
+
+                    self.scan_chunk(&memory[..read], chunk_size, chunk_start, &mut found);
+                    chunk_start += chunk_size;
+                }
+            }
+        }
+
+        Ok(PatternSetMatches {
+            matches: self.names.iter().cloned().zip(found).collect(),
+        })
+    }
+
+    // Records the matches starting within the first `chunk_size` bytes of
+    // `memory`, which was read from `address`.
+    fn scan_chunk(
+        &self,
+        memory: &[u8],
+        chunk_size: usize,
+        address: usize,
+        found: &mut [Vec<usize>],
+    ) {
+        let mut hits: Vec<(usize, usize)> = vec![];
+
+        for hit in self.automaton.find_overlapping_iter(memory) {
+            let index = self.anchored[hit.pattern().as_usize()];
+            let pattern = &self.patterns[index];
+
+            // a match starting before the chunk was seen in the previous one
+            let start = match hit.start().checked_sub(pattern.anchor().start) {
+                Some(start) => start,
+                None => continue,
+            };
+
+            if start < chunk_size
+                && start + pattern.len() <= memory.len()
+                && pattern.matches(&memory[start..])
+            {
+                hits.push((index, start));
+            }
+        }
+
+        for index in &self.unanchored {
+            let pattern = &self.patterns[*index];
+            let mut offset = 0;
+
+            while let Some(next) = pattern.find(&memory[offset..]) {
+                if offset + next >= chunk_size {
+                    break;
+                }
+                hits.push((*index, offset + next));
+                offset += next + 1;
+            }
+        }
+
+        // the automaton reports by end position, patterns with their anchor
+        // further in may come out of order
+        hits.sort_unstable();
+        for (index, start) in hits {
+            found[index].push(address + start);
+        }
+    }
+}
+
+// Every pattern of the set by name, with all of its match addresses in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatternSetMatches {
+    matches: BTreeMap<String, Vec<usize>>,
+}
+
+impl PatternSetMatches {
+    pub fn get(&self, name: &str) -> Option<&[usize]> {
+        self.matches.get(name).map(|found| found.as_slice())
+    }
+
+    // The address of a pattern that matched exactly once.
+    pub fn unique(&self, name: &str) -> Option<usize> {
+        match self.get(name) {
+            Some([address]) => Some(*address),
+            _ => None,
+        }
+    }
+
+    // Names of the patterns that did not match at all.
+    pub fn missing(&self) -> Vec<&str> {
+        self.names_where(|found| found.is_empty())
+    }
+
+    // Names of the patterns that matched more than once.
+    pub fn ambiguous(&self) -> Vec<&str> {
+        self.names_where(|found| found.len() > 1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[usize])> {
+        self.matches
+            .iter()
+            .map(|(name, found)| (name.as_str(), found.as_slice()))
+    }
+
+    pub fn into_map(self) -> BTreeMap<String, Vec<usize>> {
+        self.matches
+    }
+
+    fn names_where(&self, condition: impl Fn(&[usize]) -> bool) -> Vec<&str> {
+        self.iter()
+            .filter(|(_, found)| condition(found))
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::backend::{Buffer, Local};
+    use crate::error::Error;
+    use crate::pattern_match::PatternMatch;
+    use crate::regions::parse_maps;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_pattern_set_scan() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(
+            0x400000,
+            vec![
+                0x48, 0x8b, 0x05, 0x10, 0x20, 0x30, 0x40, 0xe8, 0x00, 0x00, 0x00, 0x00, 0x90, 0x48,
+                0x8b, 0x0d, 0x10, 0x20, 0x30, 0x40, 0xc3,
+            ],
+        );
+
+        let set = PatternSet::parse([
+            ("load", "48 8B 05 ? ? ? ?"),
+            ("load_any", "48 8b ?? 10 20"),
+            ("call", "e8 ?? ?? ?? ?? 90"),
+            ("ret", "c3"),
+            ("rex", "4? 8b"),
+            ("missing", "0f 0b"),
+        ])
+        .unwrap();
+        assert_eq!(6, set.len());
+
+        let matches = unsafe { set.scan(&buffer, buffer.base(), buffer.len()) }.unwrap();
+
+        assert_eq!(Some(0x400000), matches.unique("load"));
+        assert_eq!(Some(&[0x400000, 0x40000d][..]), matches.get("load_any"));
+        assert_eq!(Some(0x400007), matches.unique("call"));
+        assert_eq!(Some(0x400014), matches.unique("ret"));
+        assert_eq!(Some(&[0x400000, 0x40000d][..]), matches.get("rex"));
+        assert_eq!(None, matches.unique("missing"));
+
+        assert_eq!(vec!["missing"], matches.missing());
+        assert_eq!(vec!["load_any", "rex"], matches.ambiguous());
+        assert_eq!(6, matches.into_map().len());
+
+        assert!(matches!(
+            unsafe { set.scan(&buffer, 0x500000, 4) },
+            Err(Error::Unmapped(0x500000))
+        ));
+    }
+
+    #[test]
+    fn test_pattern_set_scan_module() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = vec![0xcc; 0x3000];
+        bytes[0xffe..0x1002].copy_from_slice(&[0x0f, 0x0b, 0x0f, 0x0b]);
+        bytes[0x2000..0x2002].copy_from_slice(&[0x0f, 0x0b]);
+        let buffer = Buffer::new(0x400000, bytes);
+
+        // the middle page is not readable, nothing in it is found
+        let segments = parse_maps(
+            "\
+00400000-00401000 r--p 00000000 fe:00 42                         /opt/game/game
+00401000-00402000 ---p 00001000 fe:00 42                         /opt/game/game
+00402000-00403000 r-xp 00002000 fe:00 42                         /opt/game/game
+",
+        );
+        let module = Module {
+            name: String::from("game"),
+            path: String::from("/opt/game/game"),
+            base: 0x400000,
+            size: 0x3000,
+            segments,
+        };
+
+        let set = PatternSet::parse([("ud2", "0f 0b"), ("int3", "cc cc")]).unwrap();
+        let matches = unsafe { set.scan_module(&buffer, &module) }.unwrap();
+        assert_eq!(Some(&[0x400ffe, 0x402000][..]), matches.get("ud2"));
+        assert_eq!(2 * 0xffd, matches.get("int3").unwrap().len());
+    }
+
+    #[test]
+    fn test_pattern_set_replaces_name() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let set = PatternSet::parse([("a", "01 02"), ("b", "03"), ("a", "04")]).unwrap();
+        assert_eq!(2, set.len());
+        assert_eq!(Some(&Pattern::parse("04").unwrap()), set.get("a"));
+    }
+
+    // Whatever the set finds in one pass has to be what each PatternMatch finds
+    // on its own, chunk boundaries included.
+    #[test]
+    fn test_pattern_set_matches_pattern_match() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut rng = StdRng::seed_from_u64(0x6d6e656d);
+        let memory: Vec<u8> = (0..SCAN_CHUNK_SIZE * 2 + 17)
+            .map(|_| [0x00, 0x48, 0x8b, 0xe8, 0x41][rng.gen_range(0..5)])
+            .collect();
+
+        let signatures = [
+            "48 8b ?? e8",
+            "?? ?? 8b 8b",
+            "e8 ? ? ? ? 41",
+            "4? 8b 4?",
+            "00 00 00 00 00 00",
+            "41",
+        ];
+        let set =
+            PatternSet::parse(signatures.iter().map(|signature| (*signature, *signature))).unwrap();
+        let matches = unsafe { set.scan(&Local, memory.as_ptr() as usize, memory.len()) }.unwrap();
+
+        for signature in signatures {
+            let expected: Vec<usize> =
+                PatternMatch::new(String::from(signature), memory.as_ptr(), memory.len())
+                    .matches()
+                    .offsets()
+                    .map(|offset| memory.as_ptr() as usize + offset)
+                    .collect();
+
+            assert_eq!(
+                Some(expected.as_slice()),
+                matches.get(signature),
+                "{}",
+                signature
+            );
+        }
+    }
+}
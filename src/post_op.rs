@@ -0,0 +1,163 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Steps taking a matched address to the address actually wanted, applied in
+// order by Address::try_resolve.
+//
+// In text, as in signature databases:
+//   add <n>                 Add(n), n decimal or 0x hex, possibly negative
+//   rip <offset>            PostOp::rip(offset)
+//   relative <offset> <len> Relative { offset, length }
+//   branch                  FollowBranch
+//   deref                   Deref
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PostOp {
+    // address + offset
+    Add(isize),
+    // address + length + the i32 displacement at address + offset, which is how
+    // RIP-relative operands of an instruction `length` bytes long are encoded
+    Relative { offset: usize, length: usize },
+    // the target of the E8 call or E9 jmp rel32 at address
+    FollowBranch,
+    // the pointer stored at address
+    Deref,
+}
+
+impl PostOp {
+    // The displacement of a RIP-relative operand that ends its instruction,
+    // e.g. PostOp::rip(3) for `48 8B 05 <disp32>`.
+    pub fn rip(offset: usize) -> Self {
+        PostOp::Relative {
+            offset,
+            length: offset + 4,
+        }
+    }
+}
+
+fn parse_number(number: &str) -> Option<isize> {
+    let (negative, digits) = match number.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, number.strip_prefix('+').unwrap_or(number)),
+    };
+    let hex = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"));
+
+    // from_str_radix takes a sign of its own, one is all there may be
+    if hex.unwrap_or(digits).starts_with(['+', '-']) {
+        return None;
+    }
+
+    let value = match hex {
+        Some(hex) => isize::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+impl FromStr for PostOp {
+    type Err = Error;
+
+    fn from_str(op: &str) -> Result<Self> {
+        let invalid = || Error::InvalidPostOp(op.to_string());
+        let unsigned = |number: &str| {
+            parse_number(number)
+                .and_then(|value| usize::try_from(value).ok())
+                .ok_or_else(invalid)
+        };
+
+        match op.split_whitespace().collect::<Vec<_>>()[..] {
+            ["add", offset] => Ok(PostOp::Add(parse_number(offset).ok_or_else(invalid)?)),
+            ["rip", offset] => Ok(PostOp::rip(unsigned(offset)?)),
+            ["relative", offset, length] => Ok(PostOp::Relative {
+                offset: unsigned(offset)?,
+                length: unsigned(length)?,
+            }),
+            ["branch"] => Ok(PostOp::FollowBranch),
+            ["deref"] => Ok(PostOp::Deref),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for PostOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PostOp::Add(offset) if offset < 0 => write!(f, "add -{:#x}", offset.unsigned_abs()),
+            PostOp::Add(offset) => write!(f, "add {:#x}", offset),
+            PostOp::Relative { offset, length } if length == offset + 4 => {
+                write!(f, "rip {}", offset)
+            }
+            PostOp::Relative { offset, length } => write!(f, "relative {} {}", offset, length),
+            PostOp::FollowBranch => write!(f, "branch"),
+            PostOp::Deref => write!(f, "deref"),
+        }
+    }
+}
+
+impl TryFrom<String> for PostOp {
+    type Error = Error;
+
+    fn try_from(op: String) -> Result<Self> {
+        op.parse()
+    }
+}
+
+impl From<PostOp> for String {
+    fn from(op: PostOp) -> Self {
+        op.to_string()
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    #[test]
+    fn test_post_op_from_str() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        for (text, op) in [
+            ("add 0x10", PostOp::Add(0x10)),
+            ("add -8", PostOp::Add(-8)),
+            ("add +8", PostOp::Add(8)),
+            ("rip 3", PostOp::rip(3)),
+            (
+                "relative 2 7",
+                PostOp::Relative {
+                    offset: 2,
+                    length: 7,
+                },
+            ),
+            ("branch", PostOp::FollowBranch),
+            ("  deref ", PostOp::Deref),
+        ] {
+            assert_eq!(op, text.parse().unwrap(), "{}", text);
+            assert_eq!(op, op.to_string().parse().unwrap(), "{}", text);
+        }
+
+        for text in [
+            "",
+            "add",
+            "add x",
+            "add --5",
+            "add -+5",
+            "add +-5",
+            "add -0x-5",
+            "add 0x+5",
+            "rip -1",
+            "deref 1",
+            "jump",
+        ] {
+            assert!(
+                matches!(text.parse::<PostOp>(), Err(Error::InvalidPostOp(_))),
+                "{}",
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use crate::address::Address;
+use crate::backend::MemoryBackend;
+use crate::error::{Error, Result};
+use crate::regions::{self, MemoryRegion};
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::fs::FileExt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Process {
+    pid: libc::pid_t,
+}
+
+impl Process {
+    pub fn open(pid: libc::pid_t) -> Result<Self> {
+        if pid <= 0 {
+            return Err(Error::InvalidPid(pid));
+        }
+
+        std::fs::metadata(format!("/proc/{}", pid))?;
+        Ok(Process { pid })
+    }
+
+    pub fn current() -> Self {
+        Process {
+            pid: unsafe { libc::getpid() },
+        }
+    }
+
+    pub fn pid(&self) -> libc::pid_t {
+        self.pid
+    }
+
+    pub fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let local = libc::iovec {
+            iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+        let remote = libc::iovec {
+            iov_base: address as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+
+        let result = unsafe { libc::process_vm_readv(self.pid, &local, 1, &remote, 1, 0) };
+        if result >= 0 {
+            return Ok(result as usize);
+        }
+
+        match io::Error::last_os_error() {
+            error if is_vm_unsupported(&error) => self.read_proc_mem(address, buffer),
+            error => Err(self.classify_error(address, error)),
+        }
+    }
+
+    pub fn write_bytes(&self, address: usize, bytes: &[u8]) -> Result<usize> {
+        if bytes.is_empty() {
+            return Ok(0);
+        }
+
+        let local = libc::iovec {
+            iov_base: bytes.as_ptr() as *mut libc::c_void,
+            iov_len: bytes.len(),
+        };
+        let remote = libc::iovec {
+            iov_base: address as *mut libc::c_void,
+            iov_len: bytes.len(),
+        };
+
+        let result = unsafe { libc::process_vm_writev(self.pid, &local, 1, &remote, 1, 0) };
+        if result >= 0 {
+            return Ok(result as usize);
+        }
+
+        // process_vm_writev honours page protection while /proc/<pid>/mem does not,
+        // so read-only mappings such as .text still go through the fallback.
+        match io::Error::last_os_error() {
+            error if is_vm_unsupported(&error) || error.raw_os_error() == Some(libc::EFAULT) => {
+                self.write_proc_mem(address, bytes)
+            }
+            error => Err(self.classify_error(address, error)),
+        }
+    }
+
+    // EFAULT from process_vm_* and EIO from /proc/<pid>/mem both mean the access faulted,
+    // the maps tell whether the page is missing or just protected.
+    fn classify_error(&self, address: usize, error: io::Error) -> Error {
+        match error.raw_os_error() {
+            Some(libc::EFAULT) | Some(libc::EIO) => match self.query_region(address) {
+                Ok(Some(_)) => Error::ProtectionFault(address),
+                _ => Error::Unmapped(address),
+            },
+            _ => Error::Io(error),
+        }
+    }
+
+    fn read_proc_mem(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(format!("/proc/{}/mem", self.pid))?;
+        file.read_at(buffer, address as u64)
+            .map_err(|error| self.classify_error(address, error))
+    }
+
+    fn write_proc_mem(&self, address: usize, bytes: &[u8]) -> Result<usize> {
+        let file = OpenOptions::new()
+            .write(true)
+            .open(format!("/proc/{}/mem", self.pid))?;
+        file.write_at(bytes, address as u64)
+            .map_err(|error| self.classify_error(address, error))
+    }
+}
+
+fn is_vm_unsupported(error: &io::Error) -> bool {
+    matches!(
+        error.raw_os_error(),
+        Some(libc::ENOSYS) | Some(libc::EPERM) | Some(libc::EACCES)
+    )
+}
+
+impl MemoryBackend for Process {
+    unsafe fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+        Process::read_bytes(self, address, buffer)
+    }
+
+    unsafe fn write_bytes(&self, address: usize, bytes: &[u8]) -> Result<usize> {
+        Process::write_bytes(self, address, bytes)
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        regions::of(self.pid)
+    }
+}
+
+pub type RemoteAddress = Address<Process>;
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    // Forks a copy of the test process which blocks until the returned fd is closed,
+    // then exits with 0 when `check` holds. The child shares our address layout, so
+    // addresses of locals taken before the fork are valid in it.
+    fn spawn_child(check: impl Fn() -> bool) -> (Process, libc::c_int) {
+        let mut fds = [0 as libc::c_int; 2];
+
+        unsafe {
+            assert_eq!(0, libc::pipe(fds.as_mut_ptr()));
+
+            let pid = libc::fork();
+            assert!(pid >= 0);
+
+            if pid == 0 {
+                libc::close(fds[1]);
+                let mut byte = 0u8;
+                libc::read(fds[0], std::ptr::addr_of_mut!(byte) as *mut libc::c_void, 1);
+                libc::_exit(if check() { 0 } else { 1 });
+            }
+
+            libc::close(fds[0]);
+            (Process::open(pid).unwrap(), fds[1])
+        }
+    }
+
+    fn join_child(process: Process, fd: libc::c_int) -> bool {
+        let mut status = 0;
+
+        unsafe {
+            libc::close(fd);
+            assert_eq!(process.pid(), libc::waitpid(process.pid(), &mut status, 0));
+        }
+
+        libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0
+    }
+
+    #[test]
+    fn test_process_open() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        assert_eq!(
+            Process::current(),
+            Process::open(Process::current().pid()).unwrap()
+        );
+        assert!(matches!(Process::open(-1), Err(Error::InvalidPid(-1))));
+        assert!(Process::open(libc::pid_t::MAX).is_err());
+    }
+
+    #[test]
+    fn test_remote_address_read() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let n = 0x12345678deadbeefu64;
+        let ptr = std::ptr::addr_of!(n) as *mut u8;
+        let (process, fd) = spawn_child(|| true);
+
+        unsafe {
+            let mut address = RemoteAddress::with_backend(process, ptr);
+            assert_eq!(0x12345678deadbeef, address.read::<u64>());
+            assert_eq!(0xdeadbeef, address.read::<u32>());
+            assert_eq!(vec![0xef, 0xbe, 0xad, 0xde], address.read_memory(4));
+        }
+
+        assert!(join_child(process, fd));
+    }
+
+    #[test]
+    fn test_remote_address_write() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let n = 0xdeadbeefu32;
+        let ptr = std::ptr::addr_of!(n) as *mut u8;
+        let (process, fd) =
+            spawn_child(|| unsafe { std::ptr::read_volatile(ptr as *const u32) } == 0x90345678);
+
+        unsafe {
+            let mut address = RemoteAddress::with_backend(process, ptr);
+            address.write_memory(&vec![0x78, 0x56]);
+            address.copy_memory([0xaau8, 0xbb].as_ptr(), 2);
+            assert_eq!(0xdeadbbaa, address.read::<u32>());
+
+            address.write(0x12345678u32);
+            address.fill_memory(0x90, 1);
+            assert_eq!(0x12345690, address.read::<u32>());
+            address.write_memory(&vec![0x78]);
+            RemoteAddress::with_backend(process, ptr.add(3)).write(0x90u8);
+        }
+
+        assert!(join_child(process, fd));
+        assert_eq!(0xdeadbeef, n);
+    }
+
+    #[repr(C)]
+    struct test_struct {
+        a: u8,
+        b: *const u32,
+        c: u32,
+    }
+
+    #[test]
+    fn test_remote_address_ptr_val() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let v = 0xc0cac0cau32;
+        let obj = test_struct {
+            a: 0x33,
+            b: std::ptr::addr_of!(v),
+            c: 0xbaadf00d,
+        };
+        let ptr = std::ptr::addr_of!(obj) as *mut u8;
+        let ptr_to_ptr = std::ptr::addr_of!(ptr) as *mut u8;
+        let (process, fd) = spawn_child(|| unsafe {
+            std::ptr::read_volatile(std::ptr::addr_of!(v)) == 0x11111111
+                && std::ptr::read_volatile(std::ptr::addr_of!(obj.c)) == 0x22222222
+        });
+
+        unsafe {
+            let mut address = RemoteAddress::with_backend(process, ptr_to_ptr);
+            assert_eq!(Some(0x33u8), address.read_ptr_val::<u8>(0));
+            assert_eq!(Some(0xbaadf00du32), address.read_ptr_val::<u32>(16));
+            assert_eq!(
+                Some(0xc0cac0cau32),
+                address.read_multilevel_ptr_val::<u32>(&vec![8, 0])
+            );
+
+            assert!(address.write_ptr_val::<u32>(16, 0x22222222));
+            assert!(address.write_multilevel_ptr_val::<u32>(&vec![8, 0], 0x11111111));
+
+            assert_eq!(
+                None,
+                RemoteAddress::with_backend(process, std::ptr::null_mut()).read_ptr_val::<u8>(0)
+            );
+            assert_eq!(
+                None,
+                RemoteAddress::with_backend(process, 8 as *mut u8).read_ptr_val::<u8>(0)
+            );
+        }
+
+        assert!(join_child(process, fd));
+    }
+
+    #[test]
+    fn test_process_proc_mem_fallback() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let n = 0xdeadbeefu32;
+        let ptr = std::ptr::addr_of!(n) as usize;
+        let (process, fd) =
+            spawn_child(|| unsafe { std::ptr::read_volatile(ptr as *const u32) } == 0x12345678);
+
+        let mut buffer = [0u8; 4];
+        assert_eq!(4, process.read_proc_mem(ptr, &mut buffer).unwrap());
+        assert_eq!(0xdeadbeef, u32::from_ne_bytes(buffer));
+        assert_eq!(
+            4,
+            process
+                .write_proc_mem(ptr, &0x12345678u32.to_ne_bytes())
+                .unwrap()
+        );
+
+        assert!(join_child(process, fd));
+    }
+
+    #[test]
+    fn test_process_read_unmapped() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut buffer = [0u8; 4];
+        assert!(matches!(
+            Process::current().read_bytes(8, &mut buffer),
+            Err(Error::Unmapped(8))
+        ));
+    }
+}
@@ -0,0 +1,365 @@
+use crate::backend::MemoryBackend;
+use crate::error::{Error, Result};
+#[cfg(target_os = "linux")]
+use crate::regions;
+use crate::regions::Permissions;
+#[cfg(target_os = "linux")]
+use std::io;
+use std::ops::Range;
+use std::sync::{Condvar, Mutex};
+use std::thread::ThreadId;
+
+#[cfg(target_os = "linux")]
+pub fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn page_size() -> usize {
+    0x1000
+}
+
+// The smallest page aligned range covering [address, address + size).
+pub fn page_range(address: usize, size: usize) -> Result<Range<usize>> {
+    let page_size = page_size();
+    let start = address & !(page_size - 1);
+    let end = address
+        .checked_add(size.max(1))
+        .and_then(|end| end.checked_add(page_size - 1))
+        .ok_or(Error::AddressOverflow {
+            address,
+            offset: size,
+        })?
+        & !(page_size - 1);
+
+    Ok(start..end)
+}
+
+#[cfg(target_os = "linux")]
+pub fn query(address: usize) -> Result<Permissions> {
+    regions::local()?
+        .into_iter()
+        .find(|region| region.contains(address))
+        .map(|region| region.permissions)
+        .ok_or(Error::Unmapped(address))
+}
+
+#[cfg(target_os = "linux")]
+/// # Safety
+/// Taking write or read access away from memory that is in use makes its users
+/// fault, including this program's own code and stack.
+pub unsafe fn protect(address: usize, size: usize, permissions: Permissions) -> Result<()> {
+    let pages = page_range(address, size)?;
+    let mut protection = libc::PROT_NONE;
+
+    if permissions.read {
+        protection |= libc::PROT_READ;
+    }
+    if permissions.write {
+        protection |= libc::PROT_WRITE;
+    }
+    if permissions.execute {
+        protection |= libc::PROT_EXEC;
+    }
+
+    if libc::mprotect(
+        pages.start as *mut libc::c_void,
+        pages.end - pages.start,
+        protection,
+    ) != 0
+    {
+        let error = io::Error::last_os_error();
+        return Err(match error.raw_os_error() {
+            Some(libc::ENOMEM) => Error::Unmapped(address),
+            Some(libc::EACCES) => Error::ProtectionFault(address),
+            _ => Error::Io(error),
+        });
+    }
+
+    Ok(())
+}
+
+// Held by every guard that changed a protection until it is restored. Two guards
+// on the same page, one in the freezer thread and one in a patch, would
+// otherwise each see the other's write access as the page's own, and the first
+// to drop takes it away while the second still writes. The owning thread may
+// take it again, so guards nest.
+struct GuardLock {
+    owner: Mutex<Option<(ThreadId, usize)>>,
+    released: Condvar,
+}
+
+static GUARD_LOCK: GuardLock = GuardLock {
+    owner: Mutex::new(None),
+    released: Condvar::new(),
+};
+
+impl GuardLock {
+    fn acquire(&self) {
+        let current = std::thread::current().id();
+        let mut owner = self
+            .released
+            .wait_while(
+                self.owner.lock().unwrap(),
+                |owner| matches!(owner, Some((thread, _)) if *thread != current),
+            )
+            .unwrap();
+
+        match owner.as_mut() {
+            Some((_, depth)) => *depth += 1,
+            None => *owner = Some((current, 1)),
+        }
+    }
+
+    fn release(&self) {
+        let mut owner = self.owner.lock().unwrap();
+
+        if let Some((_, depth)) = owner.as_mut() {
+            *depth -= 1;
+            if *depth == 0 {
+                *owner = None;
+                self.released.notify_all();
+            }
+        }
+    }
+}
+
+// Changes the protection of the pages covering a range and restores what each
+// of them had before when dropped.
+pub struct ProtectionGuard<'a, B: MemoryBackend> {
+    backend: &'a B,
+    restore: Vec<(Range<usize>, Permissions)>,
+    locked: bool,
+}
+
+impl<'a, B: MemoryBackend> ProtectionGuard<'a, B> {
+    /// # Safety
+    /// Same as protect, for as long as the guard lives.
+    pub unsafe fn new(
+        backend: &'a B,
+        address: usize,
+        size: usize,
+        permissions: Permissions,
+    ) -> Result<Self> {
+        ProtectionGuard::with(backend, address, size, |_| Some(permissions))
+    }
+
+    // Adds write access to whatever the pages already allow, leaving pages that
+    // are writable untouched.
+    /// # Safety
+    /// The pages stay writable while the guard lives, nothing but other guards
+    /// may change their protection in the meantime.
+    pub unsafe fn writable(backend: &'a B, address: usize, size: usize) -> Result<Self> {
+        ProtectionGuard::with(backend, address, size, |permissions| {
+            if permissions.write {
+                None
+            } else {
+                Some(Permissions {
+                    write: true,
+                    ..permissions
+                })
+            }
+        })
+    }
+
+    // Pages the backend does not know about are left alone, accessing them
+    // reports the error instead.
+    unsafe fn with(
+        backend: &'a B,
+        address: usize,
+        size: usize,
+        change: impl Fn(Permissions) -> Option<Permissions>,
+    ) -> Result<Self> {
+        let pages = page_range(address, size)?;
+        GUARD_LOCK.acquire();
+        let mut guard = ProtectionGuard {
+            backend,
+            restore: vec![],
+            locked: true,
+        };
+
+        for region in backend.regions()? {
+            let start = region.start.max(pages.start);
+            let end = region.end.min(pages.end);
+            if start >= end {
+                continue;
+            }
+
+            if let Some(permissions) = change(region.permissions) {
+                backend.protect(start, end - start, permissions)?;
+                guard.restore.push((start..end, region.permissions));
+            }
+        }
+
+        // with nothing to restore there is nothing for other guards to wait on
+        if guard.restore.is_empty() {
+            GUARD_LOCK.release();
+            guard.locked = false;
+        }
+
+        Ok(guard)
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.restore.is_empty()
+    }
+}
+
+impl<'a, B: MemoryBackend> Drop for ProtectionGuard<'a, B> {
+    fn drop(&mut self) {
+        for (range, permissions) in self.restore.drain(..) {
+            unsafe {
+                let _ = self
+                    .backend
+                    .protect(range.start, range.end - range.start, permissions);
+            }
+        }
+
+        if self.locked {
+            GUARD_LOCK.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    #[test]
+    fn test_protection_page_range() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let page_size = page_size();
+
+        assert_eq!(0..page_size, page_range(0, 1).unwrap());
+        assert_eq!(0..page_size, page_range(0, 0).unwrap());
+        assert_eq!(
+            page_size..page_size * 2,
+            page_range(page_size + 10, 20).unwrap()
+        );
+        assert_eq!(0..page_size * 2, page_range(page_size - 1, 2).unwrap());
+        assert!(matches!(
+            page_range(usize::MAX - 4, 1),
+            Err(Error::AddressOverflow { .. })
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn map_page(protection: libc::c_int) -> usize {
+        let page = libc::mmap(
+            std::ptr::null_mut(),
+            page_size() * 2,
+            protection,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_ne!(libc::MAP_FAILED, page);
+        page as usize
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_protection_protect() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        unsafe {
+            let page = map_page(libc::PROT_READ | libc::PROT_WRITE);
+            let read_only = Permissions {
+                read: true,
+                ..Permissions::default()
+            };
+
+            protect(page + 10, 1, read_only).unwrap();
+            assert_eq!(read_only, query(page).unwrap());
+            assert!(query(page + page_size()).unwrap().write);
+
+            assert!(matches!(query(8), Err(Error::Unmapped(8))));
+
+            libc::munmap(page as *mut libc::c_void, page_size() * 2);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_protection_guard() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        unsafe {
+            let page = map_page(libc::PROT_READ);
+            let read_only = query(page).unwrap();
+
+            {
+                let guard =
+                    ProtectionGuard::writable(&crate::backend::Local, page + 0x10, 4).unwrap();
+                assert!(guard.is_active());
+                assert!(query(page).unwrap().write);
+                assert_eq!(read_only, query(page + page_size()).unwrap());
+
+                *((page + 0x10) as *mut u32) = 0xdeadbeef;
+            }
+
+            assert_eq!(read_only, query(page).unwrap());
+            assert_eq!(0xdeadbeef, *((page + 0x10) as *const u32));
+
+            // already writable pages are not touched
+            let n = 0u32;
+            assert!(!ProtectionGuard::writable(
+                &crate::backend::Local,
+                std::ptr::addr_of!(n) as usize,
+                4
+            )
+            .unwrap()
+            .is_active());
+
+            libc::munmap(page as *mut libc::c_void, page_size() * 2);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_protection_guard_threads() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        unsafe {
+            let page = map_page(libc::PROT_READ);
+            let read_only = query(page).unwrap();
+
+            // each thread writes its own word of the same page, a guard dropping
+            // must never take write access from the other one
+            let threads: Vec<_> = (0..4usize)
+                .map(|thread| {
+                    std::thread::spawn(move || {
+                        let address = page + thread * 4;
+                        for n in 0..200u32 {
+                            let _guard =
+                                ProtectionGuard::writable(&crate::backend::Local, address, 4)
+                                    .unwrap();
+                            *(address as *mut u32) = n;
+                        }
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+
+            assert_eq!(read_only, query(page).unwrap());
+            for thread in 0..4 {
+                assert_eq!(199, *((page + thread * 4) as *const u32));
+            }
+
+            {
+                // guards nest within a thread
+                let outer = ProtectionGuard::writable(&crate::backend::Local, page, 4).unwrap();
+                let inner = ProtectionGuard::writable(&crate::backend::Local, page, 4).unwrap();
+                assert!(outer.is_active() && !inner.is_active());
+                drop(inner);
+                assert!(query(page).unwrap().write);
+            }
+            assert_eq!(read_only, query(page).unwrap());
+
+            libc::munmap(page as *mut libc::c_void, page_size() * 2);
+        }
+    }
+}
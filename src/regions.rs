@@ -0,0 +1,377 @@
+use crate::error::Result;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    pub shared: bool,
+}
+
+impl Permissions {
+    fn parse(permissions: &str) -> Option<Self> {
+        let permissions = permissions.as_bytes();
+        if permissions.len() != 4 {
+            return None;
+        }
+
+        Some(Permissions {
+            read: permissions[0] == b'r',
+            write: permissions[1] == b'w',
+            execute: permissions[2] == b'x',
+            shared: permissions[3] == b's',
+        })
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}",
+            if self.read { 'r' } else { '-' },
+            if self.write { 'w' } else { '-' },
+            if self.execute { 'x' } else { '-' },
+            if self.shared { 's' } else { 'p' }
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemoryRegion {
+    pub start: usize,
+    pub end: usize,
+    pub permissions: Permissions,
+    pub offset: u64,
+    pub device: (u32, u32),
+    pub inode: u64,
+    pub pathname: Option<String>,
+}
+
+impl MemoryRegion {
+    pub fn size(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        self.start <= address && address < self.end
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.permissions.read
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.permissions.write
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.permissions.execute
+    }
+
+    pub fn is_heap(&self) -> bool {
+        self.pathname.as_deref() == Some("[heap]")
+    }
+
+    // thread stacks show up as [stack:<tid>] on older kernels
+    pub fn is_stack(&self) -> bool {
+        matches!(self.pathname.as_deref(), Some(pathname) if pathname == "[stack]" || pathname.starts_with("[stack:"))
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        match self.pathname.as_deref() {
+            None => true,
+            Some(pathname) => pathname.starts_with("[anon"),
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.inode != 0
+    }
+
+    // `file` matches either the full path of the mapping or just its file name
+    pub fn is_mapped_from(&self, file: &str) -> bool {
+        match self.pathname.as_deref() {
+            Some(pathname) if self.is_file() => {
+                pathname == file
+                    || Path::new(pathname)
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        == Some(file)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RegionKind {
+    Anonymous,
+    Heap,
+    Stack,
+    File,
+}
+
+impl RegionKind {
+    fn matches(&self, region: &MemoryRegion) -> bool {
+        match self {
+            RegionKind::Anonymous => region.is_anonymous(),
+            RegionKind::Heap => region.is_heap(),
+            RegionKind::Stack => region.is_stack(),
+            RegionKind::File => region.is_file(),
+        }
+    }
+}
+
+// Every criterion left unset matches any region, so RegionFilter::new() keeps everything.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegionFilter {
+    read: Option<bool>,
+    write: Option<bool>,
+    execute: Option<bool>,
+    files: Vec<String>,
+    kinds: Vec<RegionKind>,
+}
+
+impl RegionFilter {
+    pub fn new() -> Self {
+        RegionFilter::default()
+    }
+
+    pub fn readable(mut self, read: bool) -> Self {
+        self.read = Some(read);
+        self
+    }
+
+    pub fn writable(mut self, write: bool) -> Self {
+        self.write = Some(write);
+        self
+    }
+
+    pub fn executable(mut self, execute: bool) -> Self {
+        self.execute = Some(execute);
+        self
+    }
+
+    pub fn file(mut self, file: &str) -> Self {
+        self.files.push(file.to_string());
+        self
+    }
+
+    pub fn kind(mut self, kind: RegionKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    pub fn matches(&self, region: &MemoryRegion) -> bool {
+        let permission_matches = |wanted: Option<bool>, actual: bool| match wanted {
+            Some(wanted) => wanted == actual,
+            None => true,
+        };
+
+        permission_matches(self.read, region.permissions.read)
+            && permission_matches(self.write, region.permissions.write)
+            && permission_matches(self.execute, region.permissions.execute)
+            && (self.files.is_empty() || self.files.iter().any(|file| region.is_mapped_from(file)))
+            && (self.kinds.is_empty() || self.kinds.iter().any(|kind| kind.matches(region)))
+    }
+
+    pub fn apply<I: IntoIterator<Item = MemoryRegion>>(&self, regions: I) -> Vec<MemoryRegion> {
+        regions
+            .into_iter()
+            .filter(|region| self.matches(region))
+            .collect()
+    }
+}
+
+fn next_field<'a>(line: &mut &'a str) -> Option<&'a str> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let (field, rest) = trimmed.split_at(end);
+    *line = rest;
+    Some(field)
+}
+
+// start-end perms offset major:minor inode [pathname]
+fn parse_line(mut line: &str) -> Option<MemoryRegion> {
+    let (start, end) = next_field(&mut line)?.split_once('-')?;
+    let permissions = Permissions::parse(next_field(&mut line)?)?;
+    let offset = u64::from_str_radix(next_field(&mut line)?, 16).ok()?;
+    let (major, minor) = next_field(&mut line)?.split_once(':')?;
+    let inode = next_field(&mut line)?.parse().ok()?;
+    let pathname = line.trim();
+
+    Some(MemoryRegion {
+        start: usize::from_str_radix(start, 16).ok()?,
+        end: usize::from_str_radix(end, 16).ok()?,
+        permissions,
+        offset,
+        device: (
+            u32::from_str_radix(major, 16).ok()?,
+            u32::from_str_radix(minor, 16).ok()?,
+        ),
+        inode,
+        pathname: if pathname.is_empty() {
+            None
+        } else {
+            Some(pathname.to_string())
+        },
+    })
+}
+
+// Lines that are not in the maps format are skipped.
+pub fn parse_maps(maps: &str) -> Vec<MemoryRegion> {
+    maps.lines().filter_map(parse_line).collect()
+}
+
+#[cfg(target_os = "linux")]
+pub fn local() -> Result<Vec<MemoryRegion>> {
+    Ok(parse_maps(&std::fs::read_to_string("/proc/self/maps")?))
+}
+
+#[cfg(target_os = "linux")]
+pub fn of(pid: libc::pid_t) -> Result<Vec<MemoryRegion>> {
+    Ok(parse_maps(&std::fs::read_to_string(format!(
+        "/proc/{}/maps",
+        pid
+    ))?))
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    const MAPS: &str = "\
+55bd5de47000-55bd5de49000 r--p 00000000 fe:00 317563                     /usr/bin/cat
+55bd5de49000-55bd5de4e000 r-xp 00002000 fe:00 317563                     /usr/bin/cat
+55bd5de52000-55bd5de53000 rw-p 0000a000 fe:00 317563                     /usr/bin/cat
+55bd83f95000-55bd83fb6000 rw-p 00000000 00:00 0                          [heap]
+7fdc6cf73000-7fdc6cf98000 rw-p 00000000 00:00 0
+7fdc6cf98000-7fdc6cfbe000 r--p 00000000 fe:00 395379                     /usr/lib/x86_64-linux-gnu/libc.so.6
+7fdc6cfbe000-7fdc6d114000 r-xp 00026000 fe:00 395379                     /usr/lib/x86_64-linux-gnu/libc.so.6
+7fdc6d180000-7fdc6d181000 rw-s 00000000 00:05 1234                       /dev/shm/my file (deleted)
+7ffd90a64000-7ffd90a85000 rw-p 00000000 00:00 0                          [stack]
+ffffffffff600000-ffffffffff601000 --xp 00000000 00:00 0                  [vsyscall]
+";
+
+    #[test]
+    fn test_regions_parse_maps() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let regions = parse_maps(MAPS);
+        assert_eq!(10, regions.len());
+
+        assert_eq!(
+            MemoryRegion {
+                start: 0x55bd5de49000,
+                end: 0x55bd5de4e000,
+                permissions: Permissions {
+                    read: true,
+                    write: false,
+                    execute: true,
+                    shared: false
+                },
+                offset: 0x2000,
+                device: (0xfe, 0),
+                inode: 317563,
+                pathname: Some(String::from("/usr/bin/cat")),
+            },
+            regions[1]
+        );
+        assert_eq!(None, regions[4].pathname);
+        assert_eq!(
+            Some("/dev/shm/my file (deleted)"),
+            regions[7].pathname.as_deref()
+        );
+        assert_eq!("rw-s", regions[7].permissions.to_string());
+        assert_eq!(0x5000, regions[1].size());
+        assert!(regions[1].contains(0x55bd5de4dfff));
+        assert!(!regions[1].contains(0x55bd5de4e000));
+
+        assert!(parse_maps("garbage\n").is_empty());
+    }
+
+    #[test]
+    fn test_regions_kinds() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let regions = parse_maps(MAPS);
+
+        assert!(regions[3].is_heap() && !regions[3].is_anonymous());
+        assert!(regions[4].is_anonymous() && !regions[4].is_file());
+        assert!(regions[8].is_stack());
+        assert!(regions[5].is_mapped_from("libc.so.6"));
+        assert!(regions[5].is_mapped_from("/usr/lib/x86_64-linux-gnu/libc.so.6"));
+        assert!(!regions[5].is_mapped_from("libc.so"));
+        assert!(!regions[3].is_mapped_from("[heap]"));
+    }
+
+    #[test]
+    fn test_regions_filter() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let regions = parse_maps(MAPS);
+
+        assert_eq!(
+            regions.len(),
+            RegionFilter::new().apply(regions.clone()).len()
+        );
+
+        let executable = RegionFilter::new()
+            .readable(true)
+            .executable(true)
+            .apply(regions.clone());
+        assert_eq!(
+            vec![0x55bd5de49000, 0x7fdc6cfbe000],
+            executable
+                .iter()
+                .map(|region| region.start)
+                .collect::<Vec<_>>()
+        );
+
+        let libc = RegionFilter::new()
+            .file("libc.so.6")
+            .writable(false)
+            .apply(regions.clone());
+        assert_eq!(2, libc.len());
+
+        let data = RegionFilter::new()
+            .kind(RegionKind::Heap)
+            .kind(RegionKind::Stack)
+            .kind(RegionKind::Anonymous)
+            .apply(regions);
+        assert_eq!(3, data.len());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_regions_local() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let n = 0u32;
+        let address = std::ptr::addr_of!(n) as usize;
+        let regions = local().unwrap();
+
+        let region = regions
+            .iter()
+            .find(|region| region.contains(address))
+            .unwrap();
+        assert!(region.is_readable() && region.is_writable());
+        assert!(regions.windows(2).all(|pair| pair[0].end <= pair[1].start));
+        assert!(of(std::process::id() as libc::pid_t)
+            .unwrap()
+            .iter()
+            .any(|region| region.contains(address)));
+    }
+}
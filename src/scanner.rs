@@ -0,0 +1,411 @@
+use crate::backend::MemoryBackend;
+use crate::error::Result;
+use crate::parallel::ParallelScan;
+use crate::pattern::{Pattern, SCAN_CHUNK_SIZE};
+use crate::regions::{MemoryRegion, RegionFilter};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanMatch {
+    pub address: usize,
+    pub region: MemoryRegion,
+}
+
+// Scans every readable region of a process that passes the filter.
+pub struct Scanner<B: MemoryBackend> {
+    backend: B,
+    filter: RegionFilter,
+}
+
+impl<B: MemoryBackend> Scanner<B> {
+    pub fn new(backend: B, filter: RegionFilter) -> Self {
+        Scanner { backend, filter }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn filter(&self) -> &RegionFilter {
+        &self.filter
+    }
+
+    // The regions that would be scanned, grouped into runs of adjacent regions
+    // so a match may continue from one into the next.
+    pub fn runs(&self) -> Result<Vec<Vec<MemoryRegion>>> {
+        let mut runs: Vec<Vec<MemoryRegion>> = vec![];
+
+        for region in self.backend.regions()? {
+            if !region.is_readable() || is_kernel_mapping(&region) || !self.filter.matches(&region)
+            {
+                continue;
+            }
+
+            match runs.last_mut() {
+                Some(run) if run[run.len() - 1].end == region.start => run.push(region),
+                _ => runs.push(vec![region]),
+            }
+        }
+
+        Ok(runs)
+    }
+
+    // The region list is taken once up front. With Checked or a Process, regions
+    // that can no longer be read by the time the scan reaches them are skipped.
+    // Local reads them directly and faults instead, so it is only fit for
+    // memory nothing unmaps while the scan runs.
+    pub fn scan<'a>(&'a self, pattern: &'a Pattern) -> Result<ScanMatches<'a, B>> {
+        Ok(ScanMatches {
+            backend: &self.backend,
+            pattern,
+            runs: self.runs()?,
+            run: 0,
+            next_address: None,
+            memory: vec![0u8; SCAN_CHUNK_SIZE + pattern.len() - 1],
+            memory_address: 0,
+            searched: 0,
+            offset: 0,
+        })
+    }
+
+    pub fn scan_all(&self, pattern: &Pattern) -> Result<Vec<ScanMatch>> {
+        Ok(self.scan(pattern)?.collect())
+    }
+}
+
+impl<B: MemoryBackend + Sync> Scanner<B> {
+    // Same matches in the same order as scan_all, found on several threads.
+    pub fn scan_parallel(
+        &self,
+        pattern: &Pattern,
+        parallel: &ParallelScan,
+    ) -> Result<Vec<ScanMatch>> {
+        let runs = self.runs()?;
+        let ranges: Vec<_> = runs
+            .iter()
+            .map(|run| run[0].start..run[run.len() - 1].end)
+            .collect();
+
+        Ok(parallel
+            .run(&self.backend, pattern, &ranges)?
+            .into_iter()
+            .filter_map(|(run, found)| Some((run, found.ok()?)))
+            .flat_map(|(run, found)| {
+                let run = &runs[run];
+                found.into_iter().map(move |address| ScanMatch {
+                    address,
+                    region: run
+                        .iter()
+                        .find(|region| region.contains(address))
+                        .cloned()
+                        .expect("match outside of the scanned run"),
+                })
+            })
+            .collect())
+    }
+}
+
+// [vvar] and friends are readable on paper but may fault when touched.
+fn is_kernel_mapping(region: &MemoryRegion) -> bool {
+    matches!(region.pathname.as_deref(), Some(pathname) if pathname.starts_with("[vvar") || pathname == "[vsyscall]")
+}
+
+pub struct ScanMatches<'a, B: MemoryBackend> {
+    backend: &'a B,
+    pattern: &'a Pattern,
+
+    runs: Vec<Vec<MemoryRegion>>,
+    run: usize,
+    next_address: Option<usize>,
+
+    memory: Vec<u8>,
+    memory_address: usize,
+    searched: usize,
+    offset: usize,
+}
+
+impl<'a, B: MemoryBackend> ScanMatches<'a, B> {
+    // Reads the next chunk of the current run, moving on to the next run when
+    // it is exhausted. Returns false once every run has been scanned.
+    fn next_chunk(&mut self) -> bool {
+        while self.run < self.runs.len() {
+            let run = &self.runs[self.run];
+            let run_end = run[run.len() - 1].end;
+            let address = self.next_address.unwrap_or(run[0].start);
+
+            if address >= run_end {
+                self.run += 1;
+                self.next_address = None;
+                continue;
+            }
+
+            // each chunk is read with pattern.len() - 1 extra bytes so matches
+            // straddling two chunks or two regions of the run are seen whole.
+            let chunk_size = SCAN_CHUNK_SIZE.min(run_end - address);
+            let read_size = (chunk_size + self.pattern.len() - 1).min(run_end - address);
+            self.next_address = Some(address + chunk_size);
+
+            let read = unsafe {
+                self.backend
+                    .read_bytes(address, &mut self.memory[..read_size])
+            };
+
+            if let Ok(read) = read {
+                self.memory_address = address;
+                self.searched = read.min(chunk_size + self.pattern.len() - 1);
+                self.offset = 0;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn region_of(&self, address: usize) -> MemoryRegion {
+        self.runs[self.run]
+            .iter()
+            .find(|region| region.contains(address))
+            .cloned()
+            .expect("match outside of the scanned run")
+    }
+}
+
+impl<'a, B: MemoryBackend> Iterator for ScanMatches<'a, B> {
+    type Item = ScanMatch;
+
+    fn next(&mut self) -> Option<ScanMatch> {
+        loop {
+            if self.offset < self.searched {
+                if let Some(offset) = self.pattern.find(&self.memory[self.offset..self.searched]) {
+                    let address = self.memory_address + self.offset + offset;
+                    self.offset += offset + 1;
+
+                    return Some(ScanMatch {
+                        address,
+                        region: self.region_of(address),
+                    });
+                }
+
+                self.offset = self.searched;
+            }
+
+            if !self.next_chunk() {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::error::Error;
+    use crate::regions::{parse_maps, RegionKind};
+    use std::cell::RefCell;
+
+    // Serves reads from regions parsed out of a maps listing, every region filled
+    // with zeroes until written.
+    struct Mapped {
+        regions: Vec<MemoryRegion>,
+        memory: RefCell<Vec<Vec<u8>>>,
+    }
+
+    impl Mapped {
+        fn new(maps: &str) -> Self {
+            let regions = parse_maps(maps);
+            let memory = regions
+                .iter()
+                .map(|region| vec![0u8; region.size()])
+                .collect();
+
+            Mapped {
+                regions,
+                memory: RefCell::new(memory),
+            }
+        }
+
+        fn put(&self, address: usize, bytes: &[u8]) {
+            for (i, byte) in bytes.iter().enumerate() {
+                let index = self
+                    .regions
+                    .iter()
+                    .position(|region| region.contains(address + i))
+                    .unwrap();
+                self.memory.borrow_mut()[index][address + i - self.regions[index].start] = *byte;
+            }
+        }
+    }
+
+    impl MemoryBackend for Mapped {
+        unsafe fn read_bytes(&self, address: usize, buffer: &mut [u8]) -> Result<usize> {
+            let mut read = 0;
+
+            while read < buffer.len() {
+                let index = match self
+                    .regions
+                    .iter()
+                    .position(|region| region.contains(address + read))
+                {
+                    Some(index) => index,
+                    None if read == 0 => return Err(Error::Unmapped(address)),
+                    None => break,
+                };
+
+                buffer[read] =
+                    self.memory.borrow()[index][address + read - self.regions[index].start];
+                read += 1;
+            }
+
+            Ok(read)
+        }
+
+        unsafe fn write_bytes(&self, address: usize, _bytes: &[u8]) -> Result<usize> {
+            Err(Error::ProtectionFault(address))
+        }
+
+        fn regions(&self) -> Result<Vec<MemoryRegion>> {
+            Ok(self.regions.clone())
+        }
+    }
+
+    const MAPS: &str = "\
+10000-11000 r--p 00000000 fe:00 317563                     /usr/bin/cat
+11000-23000 r-xp 00001000 fe:00 317563                     /usr/bin/cat
+23000-24000 ---p 00000000 00:00 0
+24000-25000 rw-p 00000000 00:00 0                          [heap]
+30000-31000 rw-p 00000000 00:00 0
+31000-32000 r--p 00000000 00:00 0                          [vvar]
+";
+
+    #[test]
+    fn test_scanner_runs() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mapped = Mapped::new(MAPS);
+
+        let runs = Scanner::new(&mapped, RegionFilter::new()).runs().unwrap();
+        assert_eq!(
+            vec![vec![0x10000, 0x11000], vec![0x24000], vec![0x30000]],
+            runs.iter()
+                .map(|run| run.iter().map(|region| region.start).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        );
+
+        let runs = Scanner::new(&mapped, RegionFilter::new().kind(RegionKind::File))
+            .runs()
+            .unwrap();
+        assert_eq!(1, runs.len());
+    }
+
+    #[test]
+    fn test_scanner_scan() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mapped = Mapped::new(MAPS);
+        let pattern = Pattern::parse("de ad ?? ef").unwrap();
+
+        mapped.put(0x10010, &[0xde, 0xad, 0xbe, 0xef]);
+        // straddles the r--p and r-xp regions of the same file
+        mapped.put(0x10ffe, &[0xde, 0xad, 0x00, 0xef]);
+        // straddles a chunk boundary inside a region
+        mapped.put(0x10000 + SCAN_CHUNK_SIZE - 2, &[0xde, 0xad, 0x11, 0xef]);
+        // cut off by the end of the heap
+        mapped.put(0x24ffe, &[0xde, 0xad]);
+        mapped.put(0x30ffc, &[0xde, 0xad, 0x22, 0xef]);
+
+        let matches = Scanner::new(&mapped, RegionFilter::new())
+            .scan_all(&pattern)
+            .unwrap();
+        assert_eq!(
+            vec![0x10010, 0x10ffe, 0x1fffe, 0x30ffc],
+            matches
+                .iter()
+                .map(|found| found.address)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(0x10000, matches[1].region.start);
+        assert_eq!(0x11000, matches[2].region.start);
+        assert!(matches[3].region.is_anonymous());
+
+        let executable = Scanner::new(&mapped, RegionFilter::new().executable(true))
+            .scan_all(&pattern)
+            .unwrap();
+        assert_eq!(1, executable.len());
+        assert_eq!(0x1fffe, executable[0].address);
+    }
+
+    #[test]
+    fn test_scanner_overlapping_matches() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mapped = Mapped::new(MAPS);
+        mapped.put(0x24000, &[0xaa, 0xaa, 0xaa, 0xaa]);
+
+        let matches = Scanner::new(&mapped, RegionFilter::new().kind(RegionKind::Heap))
+            .scan_all(&Pattern::parse("aa aa").unwrap())
+            .unwrap();
+        assert_eq!(
+            vec![0x24000, 0x24001, 0x24002],
+            matches
+                .iter()
+                .map(|found| found.address)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_scanner_local() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let main = crate::module::main_module(&crate::process::Process::current()).unwrap();
+        let code = test_scanner_local as *const () as usize;
+        let signature = crate::util::bytes_to_string(
+            &unsafe { crate::address::Address::new(code as *mut u8).read_memory(32) },
+            crate::util::Lettercase::Lowercase,
+            " ",
+        );
+        let pattern = Pattern::parse(&signature).unwrap();
+
+        let matches = Scanner::new(
+            crate::backend::Local,
+            RegionFilter::new().file(&main.name).executable(true),
+        )
+        .scan_all(&pattern)
+        .unwrap();
+
+        let found = matches.iter().find(|found| found.address == code).unwrap();
+        assert!(found.region.contains(code) && found.region.is_executable());
+
+        assert!(Scanner::new(
+            crate::process::Process::current(),
+            RegionFilter::new().file(&main.name).executable(true),
+        )
+        .scan(&pattern)
+        .unwrap()
+        .any(|found| found.address == code));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_scanner_scan_parallel() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let process = crate::process::Process::current();
+        let main = crate::module::main_module(&process).unwrap();
+        let scanner = Scanner::new(process, RegionFilter::new().file(&main.name));
+        // a common instruction prologue so there are plenty of matches
+        let pattern = Pattern::parse("48 89 ?? 24").unwrap();
+
+        let expected = scanner.scan_all(&pattern).unwrap();
+        assert!(!expected.is_empty());
+
+        for threads in [1, 3] {
+            assert_eq!(
+                expected,
+                scanner
+                    .scan_parallel(&pattern, &ParallelScan::new().threads(threads))
+                    .unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::backend::MemoryBackend;
+use crate::error::{Error, Result};
+use crate::pattern::Pattern;
+use crate::pattern_format::PatternFormat;
+use std::ops::Range;
+
+// Opcodes whose ModRM byte directly follows them, for spotting RIP-relative
+// operands.
+const MODRM_OPCODES: [u8; 27] = [
+    0x01, 0x03, 0x09, 0x0b, 0x21, 0x23, 0x29, 0x2b, 0x31, 0x33, 0x39, 0x3b, 0x63, 0x80, 0x81, 0x83,
+    0x84, 0x85, 0x88, 0x89, 0x8a, 0x8b, 0x8c, 0x8d, 0xc6, 0xc7, 0xff,
+];
+
+// The same for opcodes behind a 0x0f escape.
+const MODRM_OPCODES_0F: [u8; 10] = [0x10, 0x11, 0x28, 0x29, 0x2e, 0x2f, 0x6e, 0x7e, 0xb6, 0xb7];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub pattern: Pattern,
+    // how far into the pattern the address lies, PostOp::Add(offset) gets back
+    // to it from a match
+    pub offset: usize,
+}
+
+impl Signature {
+    // In the format PatternMatch::new takes.
+    pub fn to_pattern_string(&self) -> String {
+        PatternFormat::Hex.render(&self.pattern)
+    }
+}
+
+// Builds the shortest pattern that matches a given address and nothing else in
+// a range. Bytes that change when the code is loaded somewhere else, absolute
+// addresses into the range and all rel32 displacements, are wildcarded.
+pub struct SignatureGenerator<B: MemoryBackend> {
+    backend: B,
+    range: Range<usize>,
+    max_len: usize,
+    max_offset: usize,
+}
+
+impl<B: MemoryBackend> SignatureGenerator<B> {
+    // `range` has to be readable as a whole, e.g. the code segment of a module.
+    pub fn new(backend: B, range: Range<usize>) -> Self {
+        SignatureGenerator {
+            backend,
+            range,
+            max_len: 64,
+            max_offset: 0,
+        }
+    }
+
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len.max(1);
+        self
+    }
+
+    // Lets the pattern start up to `max_offset` bytes before the address when
+    // nothing unique starts at it.
+    pub fn max_offset(mut self, max_offset: usize) -> Self {
+        self.max_offset = max_offset;
+        self
+    }
+
+    pub fn generate(&self, address: usize) -> Result<Signature> {
+        if !self.range.contains(&address) {
+            return Err(Error::Unmapped(address));
+        }
+
+        let mut memory = vec![0u8; self.range.len()];
+        let read = unsafe { self.backend.read_bytes(self.range.start, &mut memory)? };
+        memory.truncate(read);
+
+        let target = address - self.range.start;
+        if target >= memory.len() {
+            return Err(Error::PartialRead {
+                address: self.range.start,
+                expected: self.range.len(),
+                read,
+            });
+        }
+
+        for offset in 0..=self.max_offset.min(target) {
+            let start = target - offset;
+            let window = &memory[start..memory.len().min(start + self.max_len)];
+            // look past the window for operands that only start inside it
+            let lookahead = &memory[start..memory.len().min(start + self.max_len + 8)];
+            let mask = relocatable_mask(lookahead, &self.range);
+
+            if let Some(len) = shortest_unique(&memory, start, window, &mask, offset + 1) {
+                let pattern = Pattern::new(window[..len].to_vec(), mask[..len].to_vec())?;
+                return Ok(Signature { pattern, offset });
+            }
+        }
+
+        Err(Error::NoUniqueSignature(address))
+    }
+}
+
+// Masks out the bytes of `code` that look like relative displacements or
+// absolute addresses inside `range`. Displacements are masked wherever they
+// lead, one into another module changes as much as one into this one.
+fn relocatable_mask(code: &[u8], range: &Range<usize>) -> Vec<u8> {
+    let mut mask = vec![0xff; code.len()];
+    let mut wildcard = |start: usize, len: usize| {
+        if start + len <= code.len() {
+            mask[start..start + len].fill(0);
+        }
+    };
+    // displacement bytes are not looked at as opcodes themselves
+    let mut displacement_end = 0;
+
+    for index in 0..code.len() {
+        let next = code.get(index + 1).copied().unwrap_or(0);
+
+        let displacement = match code[index] {
+            _ if index < displacement_end => None,
+            // call/jmp rel32
+            0xe8 | 0xe9 => Some(index + 1),
+            // jcc rel32
+            0x0f if (0x80..=0x8f).contains(&next) => Some(index + 2),
+            // two byte opcode with a RIP-relative ModRM
+            0x0f if MODRM_OPCODES_0F.contains(&next)
+                && code
+                    .get(index + 2)
+                    .is_some_and(|modrm| modrm & 0xc7 == 0x05) =>
+            {
+                Some(index + 3)
+            }
+            opcode if MODRM_OPCODES.contains(&opcode) && next & 0xc7 == 0x05 => Some(index + 2),
+            _ => None,
+        };
+        if let Some(start) = displacement {
+            wildcard(start, 4);
+            displacement_end = start + 4;
+        }
+
+        // absolute addresses, both pointer sized and 32 bit
+        if let Some(bytes) = code.get(index..index + 8) {
+            if range.contains(&(u64::from_le_bytes(bytes.try_into().unwrap()) as usize)) {
+                wildcard(index, 8);
+                continue;
+            }
+        }
+        if let Some(bytes) = code.get(index..index + 4) {
+            if range.contains(&(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)) {
+                wildcard(index, 4);
+            }
+        }
+    }
+
+    mask
+}
+
+// The length of the shortest prefix of bytes/mask, at least `min_len` long and
+// ending in a byte that is not a wildcard, which matches `memory` at `start`
+// and nowhere else. A unique prefix stays unique as it grows, so the length is
+// bisected, searching `memory` for the prefix at each step.
+fn shortest_unique(
+    memory: &[u8],
+    start: usize,
+    bytes: &[u8],
+    mask: &[u8],
+    min_len: usize,
+) -> Option<usize> {
+    let lens: Vec<usize> = (min_len..=bytes.len())
+        .filter(|len| mask[len - 1] != 0)
+        .collect();
+
+    let is_unique = |len: usize| match Pattern::new(bytes[..len].to_vec(), mask[..len].to_vec()) {
+        Ok(pattern) => {
+            pattern.find(memory) == Some(start) && pattern.find(&memory[start + 1..]).is_none()
+        }
+        Err(_) => false,
+    };
+
+    lens.get(lens.partition_point(|len| !is_unique(*len)))
+        .copied()
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::backend::Buffer;
+    use crate::pattern_match::PatternMatch;
+    use crate::post_op::PostOp;
+
+    // Two copies of the same function, differing in where they load from and
+    // in their last branch.
+    fn code() -> Buffer {
+        let mut bytes = vec![0xcc; 0x100];
+        let function = [
+            0x48, 0x8b, 0x05, 0xe9, 0x00, 0x00, 0x00, // mov rax, [rip + 0xe9]
+            0xe8, 0xf0, 0xff, 0xff, 0xff, // call
+            0x85, 0xc0, // test eax, eax
+            0x74, 0x05, // je
+        ];
+
+        bytes[0x0f] = 0x90;
+        bytes[0x10..0x20].copy_from_slice(&function);
+        bytes[0x40..0x50].copy_from_slice(&function);
+        bytes[0x43] = 0xb9;
+        bytes[0x4e] = 0x75;
+        // movabs rax, 0x400080
+        bytes[0x60..0x6a].copy_from_slice(&[0x48, 0xb8, 0x80, 0x00, 0x40, 0x00, 0, 0, 0, 0]);
+        bytes[0x6a] = 0xc3;
+        bytes[0x70..0x7a].copy_from_slice(&[0x48, 0xb8, 0x00, 0x00, 0x00, 0x00, 0, 0, 0, 0]);
+        // call into another module ; pop rbp
+        bytes[0x80..0x86].copy_from_slice(&[0xe8, 0x00, 0x00, 0x00, 0x10, 0x5d]);
+
+        Buffer::new(0x400000, bytes)
+    }
+
+    #[test]
+    fn test_signature_generate() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = code();
+        let generator = SignatureGenerator::new(&buffer, 0x400000..0x400100);
+
+        let signature = generator.generate(0x400010).unwrap();
+        assert_eq!(0, signature.offset);
+        assert_eq!(
+            "48 8b 05 ?? ?? ?? ?? e8 ?? ?? ?? ?? 85 c0 74",
+            signature.to_pattern_string()
+        );
+
+        // the absolute address is wildcarded, the unrelated zero is not
+        let signature = generator.generate(0x400060).unwrap();
+        assert_eq!(
+            "48 b8 ?? ?? ?? ?? ?? ?? ?? ?? c3",
+            signature.to_pattern_string()
+        );
+
+        // so is a displacement leading out of the range
+        assert_eq!(
+            "e8 ?? ?? ?? ?? 5d",
+            generator.generate(0x400080).unwrap().to_pattern_string()
+        );
+
+        let mut pattern_match = PatternMatch::with_backend(
+            &buffer,
+            signature.to_pattern_string(),
+            buffer.base() as *const u8,
+            buffer.len(),
+        );
+        assert_eq!(1, pattern_match.count());
+        assert_eq!(
+            0x400060 as *mut u8,
+            pattern_match
+                .find_resolved(&[PostOp::Add(signature.offset as isize)])
+                .unwrap()
+                .ptr()
+        );
+    }
+
+    #[test]
+    fn test_signature_max_offset() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = code();
+        let generator = SignatureGenerator::new(&buffer, 0x400000..0x400100).max_len(4);
+
+        assert!(matches!(
+            generator.generate(0x400010),
+            Err(Error::NoUniqueSignature(0x400010))
+        ));
+
+        let signature = generator.max_offset(4).generate(0x400010).unwrap();
+        assert_eq!(1, signature.offset);
+        assert_eq!("90 48", signature.to_pattern_string());
+
+        assert!(matches!(
+            SignatureGenerator::new(&buffer, 0x400000..0x400100).generate(0x500000),
+            Err(Error::Unmapped(0x500000))
+        ));
+    }
+}
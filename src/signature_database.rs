@@ -0,0 +1,294 @@
+use crate::backend::MemoryBackend;
+use crate::error::{Error, Result};
+use crate::module::{self, Module};
+use crate::pattern_match::PatternMatch;
+use crate::post_op::PostOp;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Signatures kept in a TOML file instead of the source, e.g.
+//
+//   [[signature]]
+//   name = "player_list"
+//   module = "game"
+//   pattern = "48 8B 05 ? ? ? ? 48 85 C0"
+//   post_ops = ["rip 3", "deref"]
+//   count = 1
+//
+// pattern is in any format pattern_format knows about, post_ops as in PostOp.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SignatureDatabase {
+    #[serde(rename = "signature", default)]
+    pub signatures: Vec<SignatureEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SignatureEntry {
+    pub name: String,
+    pub module: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub post_ops: Vec<PostOp>,
+    // how many times the pattern has to match, the first match is resolved
+    #[serde(default = "default_count")]
+    pub count: usize,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedSignature {
+    pub name: String,
+    pub module: String,
+    pub address: usize,
+}
+
+#[derive(Debug)]
+pub struct FailedSignature {
+    pub name: String,
+    pub error: Error,
+}
+
+// The outcome of every entry, in database order.
+#[derive(Debug, Default)]
+pub struct SignatureReport {
+    pub resolved: Vec<ResolvedSignature>,
+    pub failed: Vec<FailedSignature>,
+}
+
+impl SignatureReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.resolved
+            .iter()
+            .find(|resolved| resolved.name == name)
+            .map(|resolved| resolved.address)
+    }
+}
+
+impl SignatureDatabase {
+    pub fn from_toml(database: &str) -> Result<Self> {
+        toml::from_str(database).map_err(|error| Error::InvalidDatabase(error.to_string()))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|error| Error::InvalidDatabase(error.to_string()))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        SignatureDatabase::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(std::fs::write(path, self.to_toml()?)?)
+    }
+
+    // Resolves against the modules loaded in `backend`.
+    pub fn resolve<B: MemoryBackend + Clone>(&self, backend: B) -> Result<SignatureReport> {
+        let modules = module::list(&backend)?;
+        Ok(self.resolve_in(backend, &modules))
+    }
+
+    // Resolves against `modules`, matched by name or path. Entries fail on
+    // their own, one bad signature does not stop the others.
+    pub fn resolve_in<B: MemoryBackend + Clone>(
+        &self,
+        backend: B,
+        modules: &[Module],
+    ) -> SignatureReport {
+        let mut report = SignatureReport::default();
+
+        for entry in &self.signatures {
+            match entry.resolve(backend.clone(), modules) {
+                Ok(address) => report.resolved.push(ResolvedSignature {
+                    name: entry.name.clone(),
+                    module: entry.module.clone(),
+                    address,
+                }),
+                Err(error) => report.failed.push(FailedSignature {
+                    name: entry.name.clone(),
+                    error,
+                }),
+            }
+        }
+
+        report
+    }
+}
+
+impl SignatureEntry {
+    fn resolve<B: MemoryBackend + Clone>(&self, backend: B, modules: &[Module]) -> Result<usize> {
+        let module = modules
+            .iter()
+            .find(|module| module.matches(&self.module))
+            .ok_or_else(|| Error::ModuleNotFound(self.module.clone()))?;
+
+        let mut pattern_match =
+            PatternMatch::try_with_backend_in_module(backend, self.pattern.clone(), module)?;
+
+        let found = pattern_match.try_count()?;
+        if found != self.count {
+            return Err(Error::MatchCount {
+                pattern: pattern_match.pattern().to_string(),
+                expected: self.count,
+                found,
+            });
+        }
+
+        Ok(pattern_match.try_find_resolved(&self.post_ops)?.ptr() as usize)
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::backend::Buffer;
+
+    const DATABASE: &str = r#"
+[[signature]]
+name = "global"
+module = "game"
+pattern = "48 8B 05 ? ? ? ? C3"
+post_ops = ["rip 3", "deref"]
+
+[[signature]]
+name = "call_target"
+module = "game"
+pattern = "e8 ?? ?? ?? ?? 90"
+post_ops = ["branch", "add 0x1"]
+
+[[signature]]
+name = "nops"
+module = "game"
+pattern = "90"
+count = 2
+
+[[signature]]
+name = "ambiguous"
+module = "game"
+pattern = "90"
+
+[[signature]]
+name = "elsewhere"
+module = "libc.so.6"
+pattern = "90"
+"#;
+
+    fn game() -> (Buffer, Module) {
+        let mut bytes = vec![0xcc; 0x40];
+        // mov rax, [rip + 0x11] ; ret
+        bytes[..8].copy_from_slice(&[0x48, 0x8b, 0x05, 0x11, 0x00, 0x00, 0x00, 0xc3]);
+        // call 0x400030 ; nop
+        bytes[0x10..0x16].copy_from_slice(&[0xe8, 0x1b, 0x00, 0x00, 0x00, 0x90]);
+        bytes[0x18..0x20].copy_from_slice(&0xdeadbeefusize.to_le_bytes());
+        bytes[0x20] = 0x90;
+
+        let buffer = Buffer::new(0x400000, bytes);
+        let module = Module {
+            name: String::from("game"),
+            path: String::from("/opt/game/game"),
+            base: buffer.base(),
+            size: buffer.len(),
+            segments: vec![],
+        };
+
+        (buffer, module)
+    }
+
+    #[test]
+    fn test_signature_database_resolve() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let (buffer, module) = game();
+        let database = SignatureDatabase::from_toml(DATABASE).unwrap();
+        let report = database.resolve_in(&buffer, &[module]);
+
+        assert_eq!(Some(0xdeadbeef), report.get("global"));
+        assert_eq!(Some(0x400031), report.get("call_target"));
+        assert_eq!(Some(0x400015), report.get("nops"));
+        assert!(!report.is_complete());
+
+        assert_eq!(2, report.failed.len());
+        assert_eq!("ambiguous", report.failed[0].name);
+        assert!(matches!(
+            report.failed[0].error,
+            Error::MatchCount {
+                expected: 1,
+                found: 2,
+                ..
+            }
+        ));
+        assert_eq!("elsewhere", report.failed[1].name);
+        assert!(matches!(report.failed[1].error, Error::ModuleNotFound(_)));
+    }
+
+    #[test]
+    fn test_signature_database_resolve_skips_gaps() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = vec![0xcc; 0x3000];
+        bytes[0x1800..0x1802].copy_from_slice(&[0x0f, 0x0b]);
+        bytes[0x2800..0x2802].copy_from_slice(&[0x0f, 0x0b]);
+        let buffer = Buffer::new(0x400000, bytes);
+
+        // nothing is mapped between the two segments
+        let module = Module {
+            name: String::from("game"),
+            path: String::from("/opt/game/game"),
+            base: 0x400000,
+            size: 0x3000,
+            segments: crate::regions::parse_maps(
+                "\
+00400000-00401000 r--p 00000000 fe:00 42                         /opt/game/game
+00402000-00403000 r-xp 00002000 fe:00 42                         /opt/game/game
+",
+            ),
+        };
+
+        let database = SignatureDatabase::from_toml(
+            "[[signature]]\nname = \"ud2\"\nmodule = \"game\"\npattern = \"0f 0b\"",
+        )
+        .unwrap();
+        let report = database.resolve_in(&buffer, &[module]);
+        assert!(report.is_complete());
+        assert_eq!(Some(0x402800), report.get("ud2"));
+    }
+
+    #[test]
+    fn test_signature_database_toml() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let database = SignatureDatabase::from_toml(DATABASE).unwrap();
+        assert_eq!(5, database.signatures.len());
+        assert_eq!(
+            vec![PostOp::rip(3), PostOp::Deref],
+            database.signatures[0].post_ops
+        );
+        assert_eq!(1, database.signatures[0].count);
+
+        assert_eq!(
+            database,
+            SignatureDatabase::from_toml(&database.to_toml().unwrap()).unwrap()
+        );
+        assert_eq!(
+            SignatureDatabase::default(),
+            SignatureDatabase::from_toml("").unwrap()
+        );
+
+        assert!(matches!(
+            SignatureDatabase::from_toml(
+                "[[signature]]\nname = \"a\"\nmodule = \"b\"\npattern = \"90\"\npost_ops = [\"jump\"]"
+            ),
+            Err(Error::InvalidDatabase(_))
+        ));
+        assert!(matches!(
+            SignatureDatabase::from_toml("[[signature]]\nname = \"a\""),
+            Err(Error::InvalidDatabase(_))
+        ));
+    }
+}
@@ -0,0 +1,580 @@
+use crate::backend::MemoryBackend;
+use crate::error::{Error, Result};
+use crate::regions::RegionFilter;
+use crate::scanner::Scanner;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 8] = b"MNSNAP\x00\x01";
+
+// Changed bytes this close together are reported as one change, so a value
+// with some of its bytes unchanged still comes out whole.
+const MERGE_GAP: usize = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotRegion {
+    pub address: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl SnapshotRegion {
+    pub fn range(&self) -> Range<usize> {
+        self.address..self.address + self.bytes.len()
+    }
+}
+
+// The contents of some memory at one point in time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub timestamp: SystemTime,
+    pub regions: Vec<SnapshotRegion>,
+}
+
+// Interpretations of the aligned values a change touches.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueHint {
+    U32 {
+        address: usize,
+        old: u32,
+        new: u32,
+    },
+    // only when both look like floats someone would store
+    F32 {
+        address: usize,
+        old: f32,
+        new: f32,
+    },
+    // only when either side points into the captured memory
+    Pointer {
+        address: usize,
+        old: usize,
+        new: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub address: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+    pub hints: Vec<ValueHint>,
+}
+
+impl Change {
+    pub fn range(&self) -> Range<usize> {
+        self.address..self.address + self.old.len()
+    }
+}
+
+impl Snapshot {
+    // Each range is read whole, a range that can only be read in part is
+    // captured up to where reading stopped. Overlapping ranges are merged first,
+    // so no address is captured twice.
+    pub fn capture<B: MemoryBackend>(backend: &B, ranges: &[Range<usize>]) -> Result<Self> {
+        let mut sorted = ranges.to_vec();
+        sorted.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<usize>> = vec![];
+        for range in sorted.into_iter().filter(|range| !range.is_empty()) {
+            match merged.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        let mut regions = vec![];
+
+        for range in merged {
+            let mut bytes = vec![0u8; range.len()];
+            let read = unsafe { backend.read_bytes(range.start, &mut bytes)? };
+            bytes.truncate(read);
+
+            regions.push(SnapshotRegion {
+                address: range.start,
+                bytes,
+            });
+        }
+
+        Ok(Snapshot {
+            timestamp: SystemTime::now(),
+            regions,
+        })
+    }
+
+    // Every readable region passing `filter`, regions that fail to read are
+    // left out.
+    pub fn capture_filtered<B: MemoryBackend>(backend: &B, filter: RegionFilter) -> Result<Self> {
+        let mut regions = vec![];
+
+        for region in Scanner::new(backend, filter).runs()?.into_iter().flatten() {
+            let mut bytes = vec![0u8; region.size()];
+            if let Ok(read) = unsafe { backend.read_bytes(region.start, &mut bytes) } {
+                bytes.truncate(read);
+                regions.push(SnapshotRegion {
+                    address: region.start,
+                    bytes,
+                });
+            }
+        }
+
+        Ok(Snapshot {
+            timestamp: SystemTime::now(),
+            regions,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.regions.iter().map(|region| region.bytes.len()).sum()
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        self.regions
+            .iter()
+            .any(|region| region.range().contains(&address))
+    }
+
+    // The changes from self to `newer`, in address order. Only memory captured
+    // in both snapshots is compared.
+    pub fn diff(&self, newer: &Snapshot) -> Vec<Change> {
+        let mut changes = vec![];
+
+        for old in &self.regions {
+            for new in &newer.regions {
+                let start = old.address.max(new.address);
+                let end = old.range().end.min(new.range().end);
+                if start >= end {
+                    continue;
+                }
+
+                let old_bytes = &old.bytes[start - old.address..end - old.address];
+                let new_bytes = &new.bytes[start - new.address..end - new.address];
+
+                for range in changed_ranges(old_bytes, new_bytes) {
+                    changes.push(Change {
+                        address: start + range.start,
+                        old: old_bytes[range.clone()].to_vec(),
+                        new: new_bytes[range.clone()].to_vec(),
+                        hints: hints(
+                            self,
+                            newer,
+                            start + range.start..start + range.end,
+                            |address, size| {
+                                let offset = address.checked_sub(start)?;
+                                if offset + size > old_bytes.len() {
+                                    return None;
+                                }
+                                Some((
+                                    &old_bytes[offset..offset + size],
+                                    &new_bytes[offset..offset + size],
+                                ))
+                            },
+                        ),
+                    });
+                }
+            }
+        }
+
+        changes.sort_by_key(|change| change.address);
+        changes
+    }
+
+    // A compact binary form: runs of zero bytes, which most memory is full of,
+    // are stored as their length only.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let since_epoch = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&since_epoch.as_secs().to_le_bytes());
+        bytes.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+        write_varint(&mut bytes, self.regions.len() as u64);
+
+        for region in &self.regions {
+            write_varint(&mut bytes, region.address as u64);
+            write_varint(&mut bytes, region.bytes.len() as u64);
+
+            // alternating literal and zero runs, starting with a literal
+            let mut rest = &region.bytes[..];
+            while !rest.is_empty() {
+                let literal = zero_run_start(rest);
+                write_varint(&mut bytes, literal as u64);
+                bytes.extend_from_slice(&rest[..literal]);
+                rest = &rest[literal..];
+
+                let zeros = rest.iter().take_while(|byte| **byte == 0).count();
+                write_varint(&mut bytes, zeros as u64);
+                rest = &rest[zeros..];
+            }
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(reader.invalid("not a snapshot"));
+        }
+
+        let secs = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let nanos = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+        if nanos >= 1_000_000_000 {
+            return Err(reader.invalid("invalid timestamp"));
+        }
+
+        let count = reader.varint()?;
+        let mut regions = vec![];
+
+        for _ in 0..count {
+            let address = reader.varint()?;
+            let size = reader.varint()?;
+            if address.checked_add(size).is_none() {
+                return Err(reader.invalid("region overflows the address space"));
+            }
+            let mut region = Vec::with_capacity(size.min(bytes.len() * 8));
+
+            while region.len() < size {
+                let literal = reader.varint()?;
+                if literal > size - region.len() {
+                    return Err(reader.invalid("region overruns its size"));
+                }
+                region.extend_from_slice(reader.take(literal)?);
+                let zeros = reader.varint()?;
+                if literal == 0 && zeros == 0 {
+                    return Err(reader.invalid("empty run"));
+                }
+                if zeros > size - region.len() {
+                    return Err(reader.invalid("region overruns its size"));
+                }
+                region.resize(region.len() + zeros, 0);
+            }
+
+            regions.push(SnapshotRegion {
+                address,
+                bytes: region,
+            });
+        }
+
+        if reader.position != bytes.len() {
+            return Err(reader.invalid("trailing bytes"));
+        }
+
+        Ok(Snapshot {
+            timestamp: UNIX_EPOCH + Duration::new(secs, nanos),
+            regions,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(std::fs::write(path, self.to_bytes())?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Snapshot::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+// Ranges of differing bytes, merged across short stretches of equal ones.
+fn changed_ranges(old: &[u8], new: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+
+    for index in (0..old.len()).filter(|index| old[*index] != new[*index]) {
+        match ranges.last_mut() {
+            Some(range) if index - range.end <= MERGE_GAP => range.end = index + 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+
+    ranges
+}
+
+// Hints for the aligned u32 and pointer slots overlapping `range`. `values`
+// gives the old and new bytes of a slot, if the compared memory covers it.
+fn hints<'a>(
+    old_snapshot: &Snapshot,
+    new_snapshot: &Snapshot,
+    range: Range<usize>,
+    values: impl Fn(usize, usize) -> Option<(&'a [u8], &'a [u8])>,
+) -> Vec<ValueHint> {
+    let mut hints = vec![];
+
+    let mut address = range.start - range.start % 4;
+    while address < range.end {
+        if let Some((old, new)) = values(address, 4) {
+            let old = u32::from_ne_bytes(old.try_into().unwrap());
+            let new = u32::from_ne_bytes(new.try_into().unwrap());
+            hints.push(ValueHint::U32 { address, old, new });
+
+            let (old, new) = (f32::from_bits(old), f32::from_bits(new));
+            if is_plausible_float(old) && is_plausible_float(new) {
+                hints.push(ValueHint::F32 { address, old, new });
+            }
+        }
+        address += 4;
+    }
+
+    const POINTER_SIZE: usize = std::mem::size_of::<usize>();
+    let mut address = range.start - range.start % POINTER_SIZE;
+    while address < range.end {
+        if let Some((old, new)) = values(address, POINTER_SIZE) {
+            let old = usize::from_ne_bytes(old.try_into().unwrap());
+            let new = usize::from_ne_bytes(new.try_into().unwrap());
+            let points_in = |value| old_snapshot.contains(value) || new_snapshot.contains(value);
+
+            if points_in(old) || points_in(new) {
+                hints.push(ValueHint::Pointer { address, old, new });
+            }
+        }
+        address += POINTER_SIZE;
+    }
+
+    hints
+}
+
+// Zero, or a normal float of a magnitude games and the like actually use.
+// Random bits mostly land outside of that.
+fn is_plausible_float(value: f32) -> bool {
+    value == 0.0 || (value.is_normal() && (1e-4..=1e9).contains(&value.abs()))
+}
+
+// Where the next run of zeros long enough to be worth a run of its own starts.
+fn zero_run_start(bytes: &[u8]) -> usize {
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let zeros = bytes[index..].iter().take_while(|byte| **byte == 0).count();
+        if zeros >= 2 || index + zeros == bytes.len() {
+            return index;
+        }
+        index += zeros.max(1);
+    }
+
+    index
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn invalid(&self, reason: &str) -> Error {
+        Error::InvalidSnapshot(format!("{} at byte {}", reason, self.position))
+    }
+
+    fn take(&mut self, size: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() - self.position < size {
+            return Err(self.invalid("unexpected end"));
+        }
+
+        self.position += size;
+        Ok(&self.bytes[self.position - size..self.position])
+    }
+
+    fn varint(&mut self) -> Result<usize> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            // the tenth byte only has the top bit of a u64 left to give
+            if shift == 63 && byte & 0x7f > 1 {
+                return Err(self.invalid("varint too long"));
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).map_err(|_| self.invalid("value out of range"));
+            }
+        }
+
+        Err(self.invalid("varint too long"))
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::backend::Buffer;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_snapshot_diff() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![0; 0x40]);
+        let ranges = [0x1000..0x1020, 0x1030..0x1040];
+        let older = Snapshot::capture(&buffer, &ranges).unwrap();
+
+        unsafe {
+            buffer.write_bytes(0x1004, &12.5f32.to_ne_bytes()).unwrap();
+            buffer.write_bytes(0x1010, &[1, 0, 0, 2]).unwrap();
+            buffer
+                .write_bytes(0x1018, &0x1030usize.to_ne_bytes())
+                .unwrap();
+            // not captured
+            buffer.write_bytes(0x1020, &[0xff; 0x10]).unwrap();
+            buffer.write_bytes(0x103f, &[0xff]).unwrap();
+        }
+
+        let newer = Snapshot::capture(&buffer, &ranges).unwrap();
+        assert!(newer.timestamp >= older.timestamp);
+
+        let changes = older.diff(&newer);
+        assert_eq!(4, changes.len());
+
+        // 12.5 is 0x41480000, its low bytes stay zero
+        assert_eq!(0x1006..0x1008, changes[0].range());
+        assert_eq!(
+            vec![
+                ValueHint::U32 {
+                    address: 0x1004,
+                    old: 0,
+                    new: 0x41480000
+                },
+                ValueHint::F32 {
+                    address: 0x1004,
+                    old: 0.0,
+                    new: 12.5
+                },
+            ],
+            changes[0].hints
+        );
+
+        assert_eq!(0x1010..0x1014, changes[1].range());
+        assert_eq!(vec![0, 0, 0, 0], changes[1].old);
+        assert_eq!(vec![1, 0, 0, 2], changes[1].new);
+
+        assert_eq!(0x1018, changes[2].address);
+        assert!(changes[2].hints.contains(&ValueHint::Pointer {
+            address: 0x1018,
+            old: 0,
+            new: 0x1030
+        }));
+
+        assert_eq!(0x103f..0x1040, changes[3].range());
+        assert_eq!(vec![0xff], changes[3].new);
+
+        assert!(newer.diff(&newer).is_empty());
+
+        // overlapping ranges are captured, and their changes reported, once
+        let ranges = [0x1000..0x1010, 0x1008..0x1020, 0x1004..0x1008];
+        let older = Snapshot::capture(&buffer, &ranges).unwrap();
+        assert_eq!(1, older.regions.len());
+        assert_eq!(0x1000..0x1020, older.regions[0].range());
+        unsafe { buffer.write_bytes(0x100c, &[0xaa]).unwrap() };
+        let changes = older.diff(&Snapshot::capture(&buffer, &ranges).unwrap());
+        assert_eq!(1, changes.len());
+        assert_eq!(0x100c..0x100d, changes[0].range());
+    }
+
+    #[test]
+    fn test_snapshot_bytes() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut rng = StdRng::seed_from_u64(0x6d6e656d);
+        let mut bytes = vec![0u8; 0x10000];
+        for _ in 0..0x100 {
+            let offset = rng.gen_range(0..bytes.len());
+            bytes[offset] = rng.gen();
+        }
+        bytes[0x10..0x20].fill(0x41);
+
+        let buffer = Buffer::new(0x10000, bytes);
+        let snapshot = Snapshot::capture(
+            &buffer,
+            &[0x10000..0x20000, 0x10008..0x10009, 0x1ffff..0x20000],
+        )
+        .unwrap();
+
+        let serialized = snapshot.to_bytes();
+        assert!(serialized.len() < 0x1000);
+        assert_eq!(snapshot, Snapshot::from_bytes(&serialized).unwrap());
+
+        for size in 0..serialized.len() {
+            assert!(
+                matches!(
+                    Snapshot::from_bytes(&serialized[..size]),
+                    Err(Error::InvalidSnapshot(_))
+                ),
+                "{}",
+                size
+            );
+        }
+        assert!(Snapshot::from_bytes(b"MNSNAP\x00\x02").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_bytes_corrupt() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        // a single region, its runs given as (literal, zeros) pairs
+        let region = |address: u64, size: u64, runs: &[(&[u8], u64)]| {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend_from_slice(&[0; 12]);
+            write_varint(&mut bytes, 1);
+            write_varint(&mut bytes, address);
+            write_varint(&mut bytes, size);
+            for (literal, zeros) in runs {
+                write_varint(&mut bytes, literal.len() as u64);
+                bytes.extend_from_slice(literal);
+                write_varint(&mut bytes, *zeros);
+            }
+            bytes
+        };
+
+        let snapshot = Snapshot::from_bytes(&region(0x1000, 4, &[(&[1, 2], 2)])).unwrap();
+        assert_eq!(vec![1, 2, 0, 0], snapshot.regions[0].bytes);
+
+        for bytes in [
+            region(0x1000, 4, &[(&[1, 2], u64::MAX)]),
+            region(0x1000, 4, &[(&[1, 2], 3)]),
+            region(0x1000, 4, &[(&[1, 2, 3, 4, 5], 0)]),
+            region(0x1000, 4, &[(&[1], 1), (&[], 0)]),
+            region(u64::MAX, 4, &[(&[1, 2], 2)]),
+            region(u64::MAX - 1, 2, &[(&[1, 2], 0)]),
+        ] {
+            assert!(matches!(
+                Snapshot::from_bytes(&bytes),
+                Err(Error::InvalidSnapshot(_))
+            ));
+        }
+
+        // a tenth varint byte above 1 would shift bits out of the u64
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]);
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes),
+            Err(Error::InvalidSnapshot(reason)) if reason.starts_with("varint too long")
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_capture_filtered() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x1000, vec![7; 0x20]);
+        let snapshot = Snapshot::capture_filtered(&buffer, RegionFilter::new()).unwrap();
+
+        assert_eq!(0x20, snapshot.size());
+        assert!(snapshot.contains(0x101f));
+        assert!(!snapshot.contains(0x1020));
+        assert!(matches!(
+            Snapshot::capture(&buffer, std::slice::from_ref(&(0x2000..0x2004))),
+            Err(Error::Unmapped(0x2000))
+        ));
+    }
+}
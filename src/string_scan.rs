@@ -0,0 +1,233 @@
+use crate::address::Address;
+use crate::backend::MemoryBackend;
+use crate::error::{Error, Result};
+use crate::pattern::Pattern;
+use crate::pattern_match::PatternMatch;
+use crate::scanner::Scanner;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StringEncoding {
+    // fails on text that is not ASCII
+    Ascii,
+    Utf8,
+    Utf16Le,
+}
+
+// Text to find in memory, encoded into a Pattern. Case insensitivity covers the
+// ASCII letters, by masking out the bit that tells their cases apart.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StringSearch {
+    text: String,
+    encoding: StringEncoding,
+    case_insensitive: bool,
+    null_terminated: bool,
+}
+
+impl StringSearch {
+    pub fn new(text: &str, encoding: StringEncoding) -> Self {
+        StringSearch {
+            text: text.to_string(),
+            encoding,
+            case_insensitive: false,
+            null_terminated: false,
+        }
+    }
+
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    // Only matches text followed by a terminator, one zero byte or one zero
+    // UTF-16 unit.
+    pub fn null_terminated(mut self, null_terminated: bool) -> Self {
+        self.null_terminated = null_terminated;
+        self
+    }
+
+    pub fn to_pattern(&self) -> Result<Pattern> {
+        let mut bytes = vec![];
+        let mut mask = vec![];
+
+        match self.encoding {
+            StringEncoding::Ascii | StringEncoding::Utf8 => {
+                if self.encoding == StringEncoding::Ascii {
+                    if let Some(position) = self.text.find(|c: char| !c.is_ascii()) {
+                        return Err(Error::InvalidPattern {
+                            position,
+                            reason: "text is not ASCII",
+                        });
+                    }
+                }
+
+                for byte in self.text.bytes() {
+                    bytes.push(byte);
+                    mask.push(self.letter_mask(byte));
+                }
+                if self.null_terminated {
+                    bytes.push(0);
+                    mask.push(0xff);
+                }
+            }
+            StringEncoding::Utf16Le => {
+                for unit in self.text.encode_utf16() {
+                    let [low, high] = unit.to_le_bytes();
+                    bytes.extend_from_slice(&[low, high]);
+                    mask.push(if high == 0 {
+                        self.letter_mask(low)
+                    } else {
+                        0xff
+                    });
+                    mask.push(0xff);
+                }
+                if self.null_terminated {
+                    bytes.extend_from_slice(&[0, 0]);
+                    mask.extend_from_slice(&[0xff, 0xff]);
+                }
+            }
+        }
+
+        if bytes.is_empty() {
+            return Err(Error::InvalidPattern {
+                position: 0,
+                reason: "text is empty",
+            });
+        }
+
+        Pattern::new(bytes, mask)
+    }
+
+    fn letter_mask(&self, byte: u8) -> u8 {
+        if self.case_insensitive && byte.is_ascii_alphabetic() {
+            !0x20
+        } else {
+            0xff
+        }
+    }
+
+    // Every occurrence in `memory_size` bytes from `memory_start`.
+    pub fn find_in<B: MemoryBackend + Clone>(
+        &self,
+        backend: B,
+        memory_start: *const u8,
+        memory_size: usize,
+    ) -> Result<Vec<Address<B>>> {
+        PatternMatch::with_backend_from_pattern(
+            backend,
+            self.to_pattern()?,
+            memory_start,
+            memory_size,
+        )
+        .try_find_all()
+    }
+
+    // Every occurrence in the regions `scanner` covers.
+    pub fn scan<B: MemoryBackend + Clone>(&self, scanner: &Scanner<B>) -> Result<Vec<Address<B>>> {
+        let pattern = self.to_pattern()?;
+
+        Ok(scanner
+            .scan(&pattern)?
+            .map(|found| Address::with_backend(scanner.backend().clone(), found.address as *mut u8))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::backend::Buffer;
+    use crate::regions::RegionFilter;
+
+    fn found<B: MemoryBackend>(addresses: &[Address<B>]) -> Vec<usize> {
+        addresses
+            .iter()
+            .map(|address| address.ptr() as usize)
+            .collect()
+    }
+
+    #[test]
+    fn test_string_search_pattern() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        assert_eq!(
+            Pattern::parse("48 69 21").unwrap(),
+            StringSearch::new("Hi!", StringEncoding::Ascii)
+                .to_pattern()
+                .unwrap()
+        );
+        assert_eq!(
+            Pattern::parse("48 00 69 00 00 00").unwrap(),
+            StringSearch::new("Hi", StringEncoding::Utf16Le)
+                .null_terminated(true)
+                .to_pattern()
+                .unwrap()
+        );
+        assert_eq!(
+            Pattern::parse("c3 a9 00").unwrap(),
+            StringSearch::new("\u{e9}", StringEncoding::Utf8)
+                .null_terminated(true)
+                .case_insensitive(true)
+                .to_pattern()
+                .unwrap()
+        );
+
+        let pattern = StringSearch::new("a1", StringEncoding::Ascii)
+            .case_insensitive(true)
+            .to_pattern()
+            .unwrap();
+        assert_eq!(&[0xdf, 0xff], pattern.mask());
+        assert!(pattern.matches(b"A1") && pattern.matches(b"a1"));
+        assert!(!pattern.matches(b"!1"));
+
+        assert!(matches!(
+            StringSearch::new("ab\u{e9}", StringEncoding::Ascii).to_pattern(),
+            Err(Error::InvalidPattern { position: 2, .. })
+        ));
+        assert!(StringSearch::new("", StringEncoding::Utf8)
+            .to_pattern()
+            .is_err());
+    }
+
+    #[test]
+    fn test_string_search_find() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut bytes = b"xxHello\0hello world HELLO".to_vec();
+        bytes.extend("hello\0".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        bytes.extend("HeLLo w".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        let buffer = Buffer::new(0x1000, bytes);
+        let start = buffer.base() as *const u8;
+
+        let search = StringSearch::new("hello", StringEncoding::Utf8);
+        assert_eq!(
+            vec![0x1008],
+            found(&search.find_in(&buffer, start, buffer.len()).unwrap())
+        );
+
+        let search = search.case_insensitive(true);
+        assert_eq!(
+            vec![0x1002, 0x1008, 0x1014],
+            found(&search.find_in(&buffer, start, buffer.len()).unwrap())
+        );
+        assert_eq!(
+            vec![0x1002],
+            found(
+                &search
+                    .null_terminated(true)
+                    .find_in(&buffer, start, buffer.len())
+                    .unwrap()
+            )
+        );
+
+        let search = StringSearch::new("hello", StringEncoding::Utf16Le).case_insensitive(true);
+        assert_eq!(
+            vec![0x1019, 0x1025],
+            found(&search.find_in(&buffer, start, buffer.len()).unwrap())
+        );
+
+        let scanner = Scanner::new(&buffer, RegionFilter::new());
+        let mut strings = search.null_terminated(true).scan(&scanner).unwrap();
+        assert_eq!(vec![0x1019], found(&strings));
+        assert_eq!("hello", unsafe { strings[0].read_utf16_string(16) });
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::{Error, Result};
 use rand::Rng;
 use std::fmt::Write;
 
@@ -6,6 +7,7 @@ pub enum Lettercase {
     Uppercase,
 }
 
+#[allow(clippy::ptr_arg)]
 pub fn bytes_to_string(bytes: &Vec<u8>, letter_case: Lettercase, separator: &str) -> String {
     let mut result = String::new();
 
@@ -24,6 +26,7 @@ pub fn bytes_to_string(bytes: &Vec<u8>, letter_case: Lettercase, separator: &str
     result
 }
 
+#[allow(clippy::manual_is_multiple_of, clippy::is_digit_ascii_radix)]
 pub fn string_to_bytes(byte_string: &str) -> Vec<u8> {
     let mut bytes = vec![];
     let sanitized_string: String = byte_string.chars().filter(|c| !c.is_whitespace()).collect();
@@ -53,11 +56,60 @@ pub fn string_to_bytes(byte_string: &str) -> Vec<u8> {
     bytes
 }
 
+// Like string_to_bytes, but only `?` stands for a random nibble and every other
+// malformed input is reported with its byte offset in `byte_string`.
+pub fn try_string_to_bytes(byte_string: &str) -> Result<Vec<u8>> {
+    let digits: Vec<(usize, char)> = byte_string
+        .char_indices()
+        .filter(|(_, c)| !c.is_whitespace())
+        .collect();
+
+    if digits.is_empty() {
+        return Err(Error::InvalidPattern {
+            position: 0,
+            reason: "byte string is empty",
+        });
+    }
+
+    if digits.len() % 2 == 1 {
+        return Err(Error::InvalidPattern {
+            position: digits[digits.len() - 1].0,
+            reason: "incomplete byte",
+        });
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut bytes = Vec::with_capacity(digits.len() / 2);
+
+    for pair in digits.chunks(2) {
+        let mut byte = 0u8;
+
+        for (position, c) in pair {
+            let nibble = match c.to_digit(16) {
+                Some(nibble) => nibble as u8,
+                None if *c == '?' => rng.gen_range(0..16),
+                None => {
+                    return Err(Error::InvalidPattern {
+                        position: *position,
+                        reason: "invalid hex digit",
+                    })
+                }
+            };
+            byte = byte << 4 | nibble;
+        }
+
+        bytes.push(byte);
+    }
+
+    Ok(bytes)
+}
+
 #[cfg(test)]
 mod unit_test {
     use super::*;
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_util_string_to_bytes() {
         assert_eq!(
             vec![0x12, 0x34, 0x56, 0x78, 0x90, 0xab, 0xcd, 0xef],
@@ -100,4 +152,25 @@ mod unit_test {
             )
         );
     }
+
+    #[test]
+    fn test_util_try_string_to_bytes() {
+        assert_eq!(
+            vec![0x12, 0x34, 0xab],
+            try_string_to_bytes("12 34 AB").unwrap()
+        );
+        assert_eq!(0x0a, try_string_to_bytes("?a").unwrap()[0] & 0x0f);
+        assert!(matches!(
+            try_string_to_bytes(" "),
+            Err(Error::InvalidPattern { position: 0, .. })
+        ));
+        assert!(matches!(
+            try_string_to_bytes("12 3"),
+            Err(Error::InvalidPattern { position: 3, .. })
+        ));
+        assert!(matches!(
+            try_string_to_bytes("12 3g"),
+            Err(Error::InvalidPattern { position: 4, .. })
+        ));
+    }
 }
@@ -0,0 +1,685 @@
+use crate::backend::MemoryBackend;
+use crate::error::Result;
+use crate::pattern::SCAN_CHUNK_SIZE;
+use crate::regions::RegionFilter;
+use crate::scanner::Scanner;
+use std::fmt;
+use std::marker::PhantomData;
+
+// Types a ValueScan can look for, read the way Address::read reads them.
+pub trait ScanValue: Copy + PartialOrd + fmt::Debug {
+    const SIZE: usize;
+
+    // `bytes` holds at least SIZE bytes.
+    fn from_bytes(bytes: &[u8]) -> Self;
+
+    // Fills the first SIZE bytes of `bytes`, the way from_bytes reads them.
+    fn to_bytes(self, bytes: &mut [u8]);
+
+    // other added to self, wrapping for integers.
+    fn plus(self, other: Self) -> Self;
+
+    // Equality as `compare` has it, integers always compare exactly.
+    fn equals(self, other: Self, compare: FloatCompare) -> bool;
+
+    fn is_finite(self) -> bool;
+}
+
+// How floats are compared for equality, in Exact, EqualTo, Unchanged, Changed,
+// IncreasedBy and DecreasedBy. Values are often only known as a UI displays
+// them, e.g. 12.5 for anything from 12.45 to 12.55 when rounded to one decimal.
+//
+// Whatever the mode, NaN only equals NaN and an infinity only itself, so a NaN
+// that stays NaN is unchanged.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FloatCompare {
+    #[default]
+    Exact,
+    // equal once both are rounded to this many decimals
+    Rounded(u32),
+    // equal once both are truncated to this many decimals
+    Truncated(u32),
+    // at most this far apart
+    Epsilon(f64),
+    // at most this many representable values apart
+    Ulps(u64),
+}
+
+impl FloatCompare {
+    fn equals(self, a: f64, b: f64, ulps: impl Fn() -> u64) -> bool {
+        if a.is_nan() || b.is_nan() {
+            return a.is_nan() && b.is_nan();
+        }
+        if a.is_infinite() || b.is_infinite() {
+            return a == b;
+        }
+
+        match self {
+            FloatCompare::Exact => a == b,
+            FloatCompare::Rounded(decimals) => {
+                let scale = 10f64.powi(decimals as i32);
+                (a * scale).round() == (b * scale).round()
+            }
+            FloatCompare::Truncated(decimals) => {
+                let scale = 10f64.powi(decimals as i32);
+                (a * scale).trunc() == (b * scale).trunc()
+            }
+            FloatCompare::Epsilon(epsilon) => (a - b).abs() <= epsilon,
+            FloatCompare::Ulps(max) => ulps() <= max,
+        }
+    }
+}
+
+macro_rules! impl_scan_value {
+    (integer: $($t:ty),*) => {$(
+        impl ScanValue for $t {
+            const SIZE: usize = std::mem::size_of::<$t>();
+
+            fn from_bytes(bytes: &[u8]) -> Self {
+                <$t>::from_ne_bytes(bytes[..Self::SIZE].try_into().unwrap())
+            }
+
+            fn to_bytes(self, bytes: &mut [u8]) {
+                bytes[..Self::SIZE].copy_from_slice(&self.to_ne_bytes());
+            }
+
+            fn plus(self, other: Self) -> Self {
+                self.wrapping_add(other)
+            }
+
+            fn equals(self, other: Self, _compare: FloatCompare) -> bool {
+                self == other
+            }
+
+            fn is_finite(self) -> bool {
+                true
+            }
+        }
+    )*};
+    (float: $($t:ty as $bits:ty),*) => {$(
+        impl ScanValue for $t {
+            const SIZE: usize = std::mem::size_of::<$t>();
+
+            fn from_bytes(bytes: &[u8]) -> Self {
+                <$t>::from_ne_bytes(bytes[..Self::SIZE].try_into().unwrap())
+            }
+
+            fn to_bytes(self, bytes: &mut [u8]) {
+                bytes[..Self::SIZE].copy_from_slice(&self.to_ne_bytes());
+            }
+
+            fn plus(self, other: Self) -> Self {
+                self + other
+            }
+
+            fn equals(self, other: Self, compare: FloatCompare) -> bool {
+                // the bits of a float taken as sign and magnitude, mapped to
+                // integers that count up with the float
+                let ordered = |value: Self| {
+                    let bits = value.to_bits() as $bits;
+                    if bits < 0 {
+                        <$bits>::MIN.wrapping_sub(bits) as i128
+                    } else {
+                        bits as i128
+                    }
+                };
+
+                compare.equals(self as f64, other as f64, || {
+                    (ordered(self) - ordered(other)).unsigned_abs() as u64
+                })
+            }
+
+            fn is_finite(self) -> bool {
+                <$t>::is_finite(self)
+            }
+        }
+    )*};
+}
+
+impl_scan_value!(integer: u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+impl_scan_value!(float: f32 as i32, f64 as i64);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FirstScan<T> {
+    Exact(T),
+    // both ends included
+    Between(T, T),
+    // every aligned address, to be narrowed down by next scans
+    Unknown,
+}
+
+// Compares the value now at a candidate with the one seen by the previous scan.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NextScan<T> {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    IncreasedBy(T),
+    DecreasedBy(T),
+    EqualTo(T),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Comparison {
+    float: FloatCompare,
+    finite_only: bool,
+}
+
+impl Comparison {
+    fn accepts<T: ScanValue>(&self, value: T) -> bool {
+        !self.finite_only || value.is_finite()
+    }
+}
+
+impl<T: ScanValue> FirstScan<T> {
+    fn matches(&self, value: T, compare: &Comparison) -> bool {
+        compare.accepts(value)
+            && match *self {
+                FirstScan::Exact(expected) => value.equals(expected, compare.float),
+                FirstScan::Between(low, high) => low <= value && value <= high,
+                FirstScan::Unknown => true,
+            }
+    }
+}
+
+impl<T: ScanValue> NextScan<T> {
+    fn matches(&self, old: T, new: T, compare: &Comparison) -> bool {
+        let float = compare.float;
+
+        compare.accepts(new)
+            && match *self {
+                NextScan::Changed => !new.equals(old, float),
+                NextScan::Unchanged => new.equals(old, float),
+                NextScan::Increased => new > old,
+                NextScan::Decreased => new < old,
+                NextScan::IncreasedBy(by) => new.equals(old.plus(by), float),
+                NextScan::DecreasedBy(by) => new.plus(by).equals(old, float),
+                NextScan::EqualTo(expected) => new.equals(expected, float),
+            }
+    }
+}
+
+// Sparse offsets are u16, a chunk may not outgrow them.
+const _: () = assert!(SCAN_CHUNK_SIZE <= 1 << 16);
+
+// The candidates within one chunk of memory. An unknown first scan keeps the
+// whole chunk and a bit per offset, once few enough candidates are left they are
+// kept as offsets and values only.
+enum Candidates {
+    Dense { memory: Vec<u8>, bits: Vec<u64> },
+    Sparse { offsets: Vec<u16>, values: Vec<u8> },
+}
+
+struct Block {
+    address: usize,
+    count: usize,
+    candidates: Candidates,
+}
+
+impl Block {
+    fn offsets(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        match &self.candidates {
+            Candidates::Dense { bits, .. } => Box::new(set_bits(bits)),
+            Candidates::Sparse { offsets, .. } => {
+                Box::new(offsets.iter().map(|offset| *offset as usize))
+            }
+        }
+    }
+
+    fn values<T: ScanValue>(&self) -> Box<dyn Iterator<Item = (usize, T)> + '_> {
+        match &self.candidates {
+            Candidates::Dense { memory, bits } => Box::new(
+                set_bits(bits).map(move |offset| (offset, T::from_bytes(&memory[offset..]))),
+            ),
+            Candidates::Sparse { offsets, values } => Box::new(
+                offsets
+                    .iter()
+                    .zip(values.chunks_exact(T::SIZE))
+                    .map(|(offset, value)| (*offset as usize, T::from_bytes(value))),
+            ),
+        }
+    }
+
+    // Keeps the candidates whose current value passes `next`, remembering that
+    // value for the scan after. Candidates that can no longer be read are dropped.
+    unsafe fn refine<T: ScanValue, B: MemoryBackend>(
+        &mut self,
+        backend: &B,
+        next: &NextScan<T>,
+        compare: &Comparison,
+    ) {
+        let (first, end) = match &self.candidates {
+            Candidates::Dense { memory, .. } => (0, memory.len()),
+            Candidates::Sparse { offsets, .. } => (
+                offsets[0] as usize,
+                offsets[offsets.len() - 1] as usize + T::SIZE,
+            ),
+        };
+
+        let mut memory = vec![0u8; end - first];
+        let read = backend
+            .read_bytes(self.address + first, &mut memory)
+            .unwrap_or(0);
+        memory.truncate(read);
+
+        let kept: Vec<(usize, T)> = self
+            .values::<T>()
+            .filter(|(offset, old)| {
+                let offset = offset - first;
+                offset + T::SIZE <= memory.len()
+                    && next.matches(*old, T::from_bytes(&memory[offset..]), compare)
+            })
+            .map(|(offset, _)| (offset, T::from_bytes(&memory[offset - first..])))
+            .collect();
+
+        self.count = kept.len();
+        self.candidates = match self.candidates {
+            Candidates::Dense { .. } if !is_sparse_smaller::<T>(kept.len(), memory.len()) => {
+                let mut bits = vec![0u64; memory.len().div_ceil(64)];
+                for (offset, _) in &kept {
+                    bits[offset / 64] |= 1 << (offset % 64);
+                }
+                Candidates::Dense { memory, bits }
+            }
+            _ => sparse(&kept),
+        };
+    }
+}
+
+fn is_sparse_smaller<T: ScanValue>(count: usize, memory_size: usize) -> bool {
+    count * (std::mem::size_of::<u16>() + T::SIZE) < memory_size + memory_size / 8
+}
+
+fn sparse<T: ScanValue>(values: &[(usize, T)]) -> Candidates {
+    let mut offsets = Vec::with_capacity(values.len());
+    let mut bytes = vec![0u8; values.len() * T::SIZE];
+
+    for ((offset, value), slot) in values.iter().zip(bytes.chunks_exact_mut(T::SIZE)) {
+        offsets.push(*offset as u16);
+        value.to_bytes(slot);
+    }
+
+    Candidates::Sparse {
+        offsets,
+        values: bytes,
+    }
+}
+
+fn set_bits(bits: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    bits.iter().enumerate().flat_map(|(index, word)| {
+        let mut word = *word;
+        std::iter::from_fn(move || {
+            if word == 0 {
+                return None;
+            }
+            let bit = word.trailing_zeros() as usize;
+            word &= word - 1;
+            Some(index * 64 + bit)
+        })
+    })
+}
+
+// Finds where a value of type T lives: a first scan over every region passing
+// the filter, then next scans narrowing the candidates down as the value changes.
+pub struct ValueScan<T: ScanValue, B: MemoryBackend> {
+    scanner: Scanner<B>,
+    alignment: usize,
+    compare: Comparison,
+    blocks: Vec<Block>,
+    value: PhantomData<T>,
+}
+
+impl<T: ScanValue, B: MemoryBackend> ValueScan<T, B> {
+    pub fn new(backend: B, filter: RegionFilter) -> Self {
+        ValueScan {
+            scanner: Scanner::new(backend, filter),
+            alignment: T::SIZE,
+            compare: Comparison::default(),
+            blocks: vec![],
+            value: PhantomData,
+        }
+    }
+
+    // Candidates are only taken at addresses that are a multiple of
+    // `alignment`, the size of T by default.
+    pub fn alignment(mut self, alignment: usize) -> Self {
+        self.alignment = alignment.max(1);
+        self
+    }
+
+    // Ignored for integer types.
+    pub fn float_compare(mut self, compare: FloatCompare) -> Self {
+        self.compare.float = compare;
+        self
+    }
+
+    // Leaves NaN and infinities out of the candidates, which an unknown first
+    // scan over floats otherwise picks up in large numbers from random data.
+    pub fn finite_only(mut self, finite_only: bool) -> Self {
+        self.compare.finite_only = finite_only;
+        self
+    }
+
+    // Starts over, replacing any previous results. Returns the number of
+    // candidates found.
+    pub fn first_scan(&mut self, first: FirstScan<T>) -> Result<usize> {
+        self.blocks.clear();
+        let mut memory = vec![0u8; SCAN_CHUNK_SIZE + T::SIZE - 1];
+
+        for run in self.scanner.runs()? {
+            let run_end = run[run.len() - 1].end;
+            let mut address = run[0].start;
+
+            while address < run_end {
+                // values straddling two chunks belong to the first
+                let chunk_size = SCAN_CHUNK_SIZE.min(run_end - address);
+                let read_size = (chunk_size + T::SIZE - 1).min(run_end - address);
+                let read = unsafe {
+                    self.scanner
+                        .backend()
+                        .read_bytes(address, &mut memory[..read_size])
+                };
+
+                if let Ok(read) = read {
+                    if let Some(block) =
+                        self.scan_chunk(&first, address, chunk_size, &memory[..read])
+                    {
+                        self.blocks.push(block);
+                    }
+                }
+                address += chunk_size;
+            }
+        }
+
+        Ok(self.count())
+    }
+
+    fn scan_chunk(
+        &self,
+        first: &FirstScan<T>,
+        address: usize,
+        chunk_size: usize,
+        memory: &[u8],
+    ) -> Option<Block> {
+        let aligned = (self.alignment - address % self.alignment) % self.alignment;
+        let offsets = (aligned..chunk_size)
+            .step_by(self.alignment)
+            .take_while(|offset| offset + T::SIZE <= memory.len());
+
+        let block = match first {
+            FirstScan::Unknown => {
+                let mut bits = vec![0u64; memory.len().div_ceil(64)];
+                let mut count = 0;
+                for offset in
+                    offsets.filter(|offset| self.compare.accepts(T::from_bytes(&memory[*offset..])))
+                {
+                    bits[offset / 64] |= 1 << (offset % 64);
+                    count += 1;
+                }
+
+                Block {
+                    address,
+                    count,
+                    candidates: Candidates::Dense {
+                        memory: memory.to_vec(),
+                        bits,
+                    },
+                }
+            }
+            _ => {
+                let found: Vec<(usize, T)> = offsets
+                    .map(|offset| (offset, T::from_bytes(&memory[offset..])))
+                    .filter(|(_, value)| first.matches(*value, &self.compare))
+                    .collect();
+
+                Block {
+                    address,
+                    count: found.len(),
+                    candidates: sparse(&found),
+                }
+            }
+        };
+
+        (block.count > 0).then_some(block)
+    }
+
+    // Keeps the candidates passing `next`. Returns the number left.
+    pub fn next_scan(&mut self, next: NextScan<T>) -> Result<usize> {
+        let backend = self.scanner.backend();
+
+        for block in &mut self.blocks {
+            unsafe { block.refine(backend, &next, &self.compare) };
+        }
+        self.blocks.retain(|block| block.count > 0);
+
+        Ok(self.count())
+    }
+
+    pub fn count(&self) -> usize {
+        self.blocks.iter().map(|block| block.count).sum()
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.offsets().map(move |offset| block.address + offset))
+    }
+
+    // Every candidate with the value it had at the last scan.
+    pub fn results(&self) -> impl Iterator<Item = (usize, T)> + '_ {
+        self.blocks.iter().flat_map(|block| {
+            block
+                .values::<T>()
+                .map(move |(offset, value)| (block.address + offset, value))
+        })
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+    use crate::backend::Buffer;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn write<T: ScanValue>(buffer: &Buffer, address: usize, value: T) {
+        let mut bytes = vec![0u8; T::SIZE];
+        value.to_bytes(&mut bytes);
+        unsafe { buffer.write_bytes(address, &bytes).unwrap() };
+    }
+
+    #[test]
+    fn test_value_scan_exact() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x10000, vec![0; SCAN_CHUNK_SIZE * 2 + 0x100]);
+        write(&buffer, 0x10010, 1337u32);
+        write(&buffer, 0x10021, 1337u32);
+        // straddles the chunk boundary
+        write(&buffer, 0x10000 + SCAN_CHUNK_SIZE - 2, 1337u32);
+        write(&buffer, 0x10080, 1000u32);
+
+        let mut scan = ValueScan::<u32, _>::new(&buffer, RegionFilter::new());
+        assert_eq!(1, scan.first_scan(FirstScan::Exact(1337)).unwrap());
+
+        let mut unaligned = ValueScan::<u32, _>::new(&buffer, RegionFilter::new()).alignment(1);
+        assert_eq!(3, unaligned.first_scan(FirstScan::Exact(1337)).unwrap());
+        assert_eq!(
+            vec![0x10010, 0x10021, 0x10000 + SCAN_CHUNK_SIZE - 2],
+            unaligned.addresses().collect::<Vec<_>>()
+        );
+
+        assert_eq!(2, scan.first_scan(FirstScan::Between(1000, 2000)).unwrap());
+
+        write(&buffer, 0x10010, 1338u32);
+        write(&buffer, 0x10080, 990u32);
+        assert_eq!(1, scan.next_scan(NextScan::Increased).unwrap());
+        assert_eq!(vec![(0x10010, 1338)], scan.results().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_value_scan_unknown() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x10000, vec![0; SCAN_CHUNK_SIZE + 0x100]);
+        let mut scan = ValueScan::<i32, _>::new(&buffer, RegionFilter::new());
+
+        assert_eq!(
+            (SCAN_CHUNK_SIZE + 0x100) / 4,
+            scan.first_scan(FirstScan::Unknown).unwrap()
+        );
+        assert_eq!(
+            (SCAN_CHUNK_SIZE + 0x100) / 4,
+            scan.next_scan(NextScan::Unchanged).unwrap()
+        );
+
+        write(&buffer, 0x10100, 5i32);
+        write(&buffer, 0x10200, -5i32);
+        assert_eq!(2, scan.next_scan(NextScan::Changed).unwrap());
+
+        write(&buffer, 0x10100, 15i32);
+        write(&buffer, 0x10200, -4i32);
+        assert_eq!(1, scan.next_scan(NextScan::IncreasedBy(10)).unwrap());
+        assert_eq!(vec![(0x10100, 15)], scan.results().collect::<Vec<_>>());
+
+        write(&buffer, 0x10100, 12i32);
+        assert_eq!(1, scan.next_scan(NextScan::DecreasedBy(3)).unwrap());
+        assert_eq!(1, scan.next_scan(NextScan::EqualTo(12)).unwrap());
+        assert_eq!(0, scan.next_scan(NextScan::Decreased).unwrap());
+        assert_eq!(0, scan.addresses().count());
+    }
+
+    #[test]
+    fn test_value_scan_float() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x10000, vec![0; 0x100]);
+        write(&buffer, 0x10008, 12.5f64);
+        write(&buffer, 0x10010, 100.0f64);
+
+        let mut scan = ValueScan::<f64, _>::new(&buffer, RegionFilter::new());
+        assert_eq!(1, scan.first_scan(FirstScan::Exact(12.5)).unwrap());
+
+        write(&buffer, 0x10008, 13.0f64);
+        assert_eq!(1, scan.next_scan(NextScan::IncreasedBy(0.5)).unwrap());
+
+        assert_eq!(2, scan.first_scan(FirstScan::Between(10.0, 200.0)).unwrap());
+    }
+
+    #[test]
+    fn test_float_compare() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        assert!(12.46f32.equals(12.5, FloatCompare::Rounded(1)));
+        assert!(!12.44f32.equals(12.5, FloatCompare::Rounded(1)));
+        assert!(12.59f64.equals(12.5, FloatCompare::Truncated(1)));
+        assert!(!12.46f64.equals(12.5, FloatCompare::Truncated(1)));
+        assert!(12.501f32.equals(12.5, FloatCompare::Epsilon(0.01)));
+        assert!(!12.52f32.equals(12.5, FloatCompare::Epsilon(0.01)));
+
+        let next = f32::from_bits(1.0f32.to_bits() + 1);
+        assert!(!next.equals(1.0, FloatCompare::Exact));
+        assert!(next.equals(1.0, FloatCompare::Ulps(1)));
+        assert!(!f32::from_bits(1.0f32.to_bits() + 2).equals(1.0, FloatCompare::Ulps(1)));
+        // the smallest values either side of zero are two steps apart
+        assert!((-f64::from_bits(1)).equals(f64::from_bits(1), FloatCompare::Ulps(2)));
+        assert!(0.0f64.equals(-0.0, FloatCompare::Ulps(0)));
+
+        for compare in [
+            FloatCompare::Exact,
+            FloatCompare::Rounded(0),
+            FloatCompare::Epsilon(f64::MAX),
+            FloatCompare::Ulps(u64::MAX),
+        ] {
+            assert!(f32::NAN.equals(f32::NAN, compare));
+            assert!(!f32::NAN.equals(1.0, compare));
+            assert!(f64::INFINITY.equals(f64::INFINITY, compare));
+            assert!(!f64::INFINITY.equals(f64::MAX, compare));
+            assert!(!f64::INFINITY.equals(f64::NEG_INFINITY, compare));
+        }
+
+        assert!(5u32.equals(5, FloatCompare::Epsilon(1.0)));
+        assert!(!5u32.equals(6, FloatCompare::Epsilon(1.0)));
+    }
+
+    #[test]
+    fn test_value_scan_float_compare() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let buffer = Buffer::new(0x10000, vec![0; 0x40]);
+        write(&buffer, 0x10000, 12.46f32);
+        write(&buffer, 0x10004, 12.54f32);
+        write(&buffer, 0x10008, 12.56f32);
+        write(&buffer, 0x1000c, f32::NAN);
+        write(&buffer, 0x10010, f32::INFINITY);
+
+        let mut scan = ValueScan::<f32, _>::new(&buffer, RegionFilter::new())
+            .float_compare(FloatCompare::Rounded(1));
+        assert_eq!(2, scan.first_scan(FirstScan::Exact(12.5)).unwrap());
+
+        let mut scan = ValueScan::<f32, _>::new(&buffer, RegionFilter::new())
+            .float_compare(FloatCompare::Truncated(1));
+        assert_eq!(2, scan.first_scan(FirstScan::Exact(12.5)).unwrap());
+        assert_eq!(vec![0x10004, 0x10008], scan.addresses().collect::<Vec<_>>());
+
+        // a NaN that stays NaN is unchanged
+        let mut scan = ValueScan::<f32, _>::new(&buffer, RegionFilter::new());
+        assert_eq!(16, scan.first_scan(FirstScan::Unknown).unwrap());
+        assert_eq!(16, scan.next_scan(NextScan::Unchanged).unwrap());
+
+        let mut scan = ValueScan::<f32, _>::new(&buffer, RegionFilter::new()).finite_only(true);
+        assert_eq!(14, scan.first_scan(FirstScan::Unknown).unwrap());
+
+        write(&buffer, 0x10000, 12.4601f32);
+        write(&buffer, 0x10004, 13.0f32);
+        scan = scan.float_compare(FloatCompare::Epsilon(0.001));
+        assert_eq!(13, scan.next_scan(NextScan::Unchanged).unwrap());
+        assert_eq!(1, scan.next_scan(NextScan::EqualTo(12.46)).unwrap());
+    }
+
+    // Next scans have to agree with filtering the results by hand, however the
+    // candidates of a block happen to be stored.
+    #[test]
+    fn test_value_scan_random() {
+        std::env::set_var("RUST_BACKTRACE", "1");
+
+        let mut rng = StdRng::seed_from_u64(0x6d6e656d);
+        let size = SCAN_CHUNK_SIZE * 2 + 0x40;
+        let buffer = Buffer::new(0x10000, (0..size).map(|_| rng.gen_range(0..4)).collect());
+
+        let mut scan = ValueScan::<u16, _>::new(&buffer, RegionFilter::new()).alignment(1);
+        let bytes = buffer.to_vec();
+        let mut expected: Vec<(usize, u16)> = (0..size - 1)
+            .map(|offset| (0x10000 + offset, u16::from_bytes(&bytes[offset..])))
+            .collect();
+        scan.first_scan(FirstScan::Unknown).unwrap();
+
+        for next in [
+            NextScan::Unchanged,
+            NextScan::Changed,
+            NextScan::Increased,
+            NextScan::Unchanged,
+        ] {
+            for _ in 0..size / 4 {
+                let offset = rng.gen_range(0..size);
+                unsafe {
+                    buffer
+                        .write_bytes(0x10000 + offset, &[rng.gen_range(0..4)])
+                        .unwrap();
+                }
+            }
+
+            let bytes = buffer.to_vec();
+            expected = expected
+                .into_iter()
+                .map(|(address, old)| (address, old, u16::from_bytes(&bytes[address - 0x10000..])))
+                .filter(|(_, old, new)| next.matches(*old, *new, &Comparison::default()))
+                .map(|(address, _, new)| (address, new))
+                .collect();
+
+            assert_eq!(expected.len(), scan.next_scan(next).unwrap());
+            assert_eq!(expected, scan.results().collect::<Vec<_>>());
+        }
+    }
+}